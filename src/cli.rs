@@ -1,7 +1,7 @@
 use std::{error::Error, path::Path};
 
 use clap::{Arg, Command};
-use imfconv::imfconv::{ColorProfile, ImageType};
+use imfconv::imfconv::{ColorProfile, ImageType, Threshold, ToneMapping};
 
 mod directory;
 mod file;
@@ -10,6 +10,8 @@ const APP_NAME: &str = "imfconv";
 const VERSION: &str = "3.0.0";
 const AUTHOR: &str = "hkiku482 <h.kikuchi482@gmail.com>";
 const ABOUT: &str = "Convert image format.";
+/// Path that stands for the standard input or output.
+const STDIO: &str = "-";
 
 trait CliImfconv {
     /// Image format conversion for file or directory.
@@ -39,12 +41,60 @@ impl ImageFormat {
     const JPEG: &str = "jpg";
     const PNG: &str = "png";
     const TIFF: &str = "tif";
+    const WEBP: &str = "webp";
+    const AVIF: &str = "avif";
+    const BMP: &str = "bmp";
+    const GIF: &str = "gif";
+    const ICO: &str = "ico";
+    const QOI: &str = "qoi";
+    const EXR: &str = "exr";
+    const TGA: &str = "tga";
+    const PNM: &str = "pnm";
+    const FARBFELD: &str = "ff";
+    const DDS: &str = "dds";
+    const PDF: &str = "pdf";
+    const APNG: &str = "apng";
+    #[cfg(feature = "jxl")]
+    const JXL: &str = "jxl";
+
+    /// Every format accepted by `--format`.
+    fn values() -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut values = vec![
+            Self::JPEG,
+            Self::PNG,
+            Self::TIFF,
+            Self::WEBP,
+            Self::AVIF,
+            Self::BMP,
+            Self::GIF,
+            Self::ICO,
+            Self::QOI,
+            Self::EXR,
+            Self::TGA,
+            Self::PNM,
+            Self::FARBFELD,
+            Self::DDS,
+            Self::PDF,
+            Self::APNG,
+        ];
+        #[cfg(feature = "jxl")]
+        values.push(Self::JXL);
+        values
+    }
 }
 
 struct ImageColorProfile;
 impl ImageColorProfile {
     const GRAYSCALE: &str = "grayscale";
+    const GRAYSCALE_ALPHA: &str = "grayscale-alpha";
     const RGB: &str = "rgb";
+    const RGBA: &str = "rgba";
+    const CMYK: &str = "cmyk";
+    const SEPIA: &str = "sepia";
+    const INVERT: &str = "invert";
+    const TONE_MAP: &str = "tone-map";
+    const BILEVEL: &str = "bilevel";
 }
 
 /// execute imfconv for cli
@@ -55,7 +105,7 @@ pub fn run() {
         .about(ABOUT)
         .arg(
             Arg::new(OsArgsId::SOURCE_ITEM_PATH)
-                .help("The source image file or directory.")
+                .help("The source image file or directory, or - to read stdin and write stdout.")
                 .num_args(1..)
                 .required(true),
         )
@@ -65,7 +115,7 @@ pub fn run() {
                 .short('f')
                 .help("Output file format.")
                 .ignore_case(true)
-                .value_parser([ImageFormat::JPEG, ImageFormat::PNG, ImageFormat::TIFF])
+                .value_parser(ImageFormat::values())
                 .default_value(ImageFormat::PNG),
         )
         .arg(
@@ -74,7 +124,17 @@ pub fn run() {
                 .short('c')
                 .help("Image color profile.")
                 .ignore_case(true)
-                .value_parser([ImageColorProfile::GRAYSCALE, ImageColorProfile::RGB])
+                .value_parser([
+                    ImageColorProfile::GRAYSCALE,
+                    ImageColorProfile::GRAYSCALE_ALPHA,
+                    ImageColorProfile::RGB,
+                    ImageColorProfile::RGBA,
+                    ImageColorProfile::CMYK,
+                    ImageColorProfile::SEPIA,
+                    ImageColorProfile::INVERT,
+                    ImageColorProfile::TONE_MAP,
+                    ImageColorProfile::BILEVEL,
+                ])
                 .default_value(ImageColorProfile::RGB),
         )
         .arg(
@@ -99,13 +159,35 @@ pub fn run() {
     let format = match extension {
         ImageFormat::JPEG => ImageType::JPEG,
         ImageFormat::TIFF => ImageType::TIFF,
+        ImageFormat::WEBP => ImageType::WEBP,
+        ImageFormat::AVIF => ImageType::AVIF,
+        ImageFormat::BMP => ImageType::BMP,
+        ImageFormat::GIF => ImageType::GIF,
+        ImageFormat::ICO => ImageType::ICO,
+        ImageFormat::QOI => ImageType::QOI,
+        ImageFormat::EXR => ImageType::EXR,
+        ImageFormat::TGA => ImageType::TGA,
+        ImageFormat::PNM => ImageType::PNM,
+        ImageFormat::FARBFELD => ImageType::FARBFELD,
+        ImageFormat::DDS => ImageType::DDS,
+        ImageFormat::PDF => ImageType::PDF,
+        ImageFormat::APNG => ImageType::APNG,
+        #[cfg(feature = "jxl")]
+        ImageFormat::JXL => ImageType::JXL,
         _ => ImageType::PNG,
     };
 
     let profile = match command.get_one::<String>(OsArgsId::COLOR_PROFILE) {
         Some(p) => match p.as_str() {
             ImageColorProfile::GRAYSCALE => ColorProfile::GRAYSCALE,
+            ImageColorProfile::GRAYSCALE_ALPHA => ColorProfile::GRAYSCALE_ALPHA,
             ImageColorProfile::RGB => ColorProfile::RGB,
+            ImageColorProfile::RGBA => ColorProfile::RGBA,
+            ImageColorProfile::CMYK => ColorProfile::CMYK,
+            ImageColorProfile::SEPIA => ColorProfile::SEPIA,
+            ImageColorProfile::INVERT => ColorProfile::INVERT,
+            ImageColorProfile::TONE_MAP => ColorProfile::TONE_MAP(ToneMapping::ACES),
+            ImageColorProfile::BILEVEL => ColorProfile::BILEVEL(Threshold::OTSU),
             _ => ColorProfile::RGB,
         },
         None => ColorProfile::RGB,
@@ -117,13 +199,21 @@ pub fn run() {
             None => "",
         };
 
+        // A source of `-` is read from stdin and written to stdout, for use in pipelines.
+        if source_path == STDIO {
+            if let Err(e) = file::AsFile.exec(STDIO, STDIO, &format, &profile) {
+                eprintln!("{}", e);
+            }
+            continue;
+        }
+
         let (handler, output_path): (Box<dyn CliImfconv>, String) =
             if Path::new(source_path).is_dir() {
                 match output {
                     "" => {
                         let mut output_dir = String::from(source_path);
-                        output_dir.push_str("_");
-                        output_dir.push_str(&extension);
+                        output_dir.push('_');
+                        output_dir.push_str(extension);
                         let p = Path::new(&output_dir);
                         let p = p.to_str();
                         match p {
@@ -136,7 +226,7 @@ pub fn run() {
             } else {
                 match output {
                     "" => {
-                        let p = Path::new(&source_path).with_extension(&extension);
+                        let p = Path::new(&source_path).with_extension(extension);
                         let p = p.to_str();
                         match p {
                             Some(s) => (Box::new(file::AsFile), String::from(s)),
@@ -149,7 +239,7 @@ pub fn run() {
 
         match handler.exec(source_path, &output_path, &format, &profile) {
             Ok(_) => continue,
-            Err(e) => eprint!("{}\n", e),
+            Err(e) => eprintln!("{}", e),
         };
     }
 }
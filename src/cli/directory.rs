@@ -1,11 +1,7 @@
-use std::{
-    fs::create_dir_all,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
-use imfconv::imfconv::Imfconv;
+use imfconv::imfconv::ImfconvBatch;
 use rand::Rng;
-use walkdir::WalkDir;
 
 use super::CliImfconv;
 
@@ -40,9 +36,7 @@ impl CliImfconv for AsDirectory {
         format: &imfconv::imfconv::ImageType,
         profile: &imfconv::imfconv::ColorProfile,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let src_path_root = Path::new(src);
-
-        let origin = if dest == "" {
+        let origin = if dest.is_empty() {
             Path::new(src)
         } else {
             Path::new(dest)
@@ -56,45 +50,13 @@ impl CliImfconv for AsDirectory {
             Err(_) => origin.to_path_buf(),
         };
 
-        match create_dir_all(&dest_path_root) {
-            Ok(_) => {}
-            Err(_) => todo!(),
-        };
-
-        for entry in WalkDir::new(src) {
-            let source_path = match entry {
-                Ok(p) => p,
-                Err(_) => todo!(),
-            };
-            let source_path = source_path.path();
-
-            if !source_path.is_file() {
-                continue;
-            }
-
-            let related_source_image_path = match Path::new(source_path).strip_prefix(src_path_root)
-            {
-                Ok(p) => p,
-                Err(_) => todo!(),
-            };
-
-            let dest_path = Path::new(&dest_path_root)
-                .join(&related_source_image_path)
-                .with_extension("");
-
-            println!("{:?}", source_path);
-            println!("{:?}", dest_path);
-            let handler = match Imfconv::new(&source_path, &dest_path.as_path()) {
-                Ok(h) => match h.set_image_format(format).set_color_profile(profile) {
-                    Ok(h) => h,
-                    Err(_) => todo!(),
-                },
-                Err(_) => todo!(),
-            };
-            match handler.convert() {
-                Ok(_) => continue,
-                Err(_) => todo!(),
-            };
+        let batch = ImfconvBatch::new(Path::new(src), &dest_path_root)?
+            .set_recursive(true)
+            .set_continue_on_error(true)
+            .set_image_format(format)
+            .set_color_profile(profile);
+        for failure in batch.convert()?.failed {
+            eprintln!("{}: {}", failure.source.display(), failure.error);
         }
         Ok(())
     }
@@ -1,6 +1,6 @@
 use std::path::{Path, PathBuf};
 
-use super::CliImfconv;
+use super::{CliImfconv, STDIO};
 use imfconv::imfconv;
 use rand::Rng;
 
@@ -34,10 +34,16 @@ impl CliImfconv for AsFile {
         format: &imfconv::ImageType,
         profile: &imfconv::ColorProfile,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        if src == STDIO {
+            let builder = imfconv::Imfconv::from_stdin(Path::new(dest))?;
+            let builder = builder.set_image_format(format);
+            let builder = builder.set_color_profile(profile)?;
+            return builder.convert();
+        }
         let src_path = Path::new(src);
 
         // Decide the destination path from source path.
-        let dst_path = if dest == "" {
+        let dst_path = if dest.is_empty() {
             Path::new(src)
         } else {
             Path::new(dest)
@@ -56,15 +62,9 @@ impl CliImfconv for AsFile {
             },
             Err(_) => dst_path.to_path_buf(),
         };
-        let builder = match imfconv::Imfconv::new(src_path, &dst_path) {
-            Ok(b) => b,
-            Err(e) => return Err(e),
-        };
+        let builder = imfconv::Imfconv::new(src_path, &dst_path)?;
         let builder = builder.set_image_format(format);
-        let builder = match builder.set_color_profile(profile) {
-            Ok(p) => p,
-            Err(e) => return Err(e),
-        };
+        let builder = builder.set_color_profile(profile)?;
         builder.convert()
     }
 }
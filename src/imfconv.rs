@@ -1,124 +1,1905 @@
 use std::{
+    borrow::Cow,
     error::Error,
+    fs,
+    io::{self, Read, Write},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::DynamicImage;
+
 use self::{
     handler::{
-        color_profile::{grayscale::Grayscale, rgb::RgbColor, ImfconvColorProfile},
-        format::{jpeg::JpegHandler, png::PngHandler, tiff::TiffHandler, ImfconvHandler},
+        animation::{Animation, AnimationFrame},
+        color_profile::{
+            bilevel::Bilevel, cmyk::CmykColor, duotone::Duotone, grayscale::Grayscale,
+            grayscale_alpha::GrayscaleAlpha, invert::Invert, posterize::Posterize, rgb::RgbColor,
+            rgba::RgbaColor, sepia::Sepia, tone_map::ToneMap,
+        },
+        color_space::{convert, icc_profile},
+        composite::composite,
+        flatten,
+        format::{
+            apng::ApngHandler, avif::AvifHandler, bmp::BmpHandler, dds::DdsHandler,
+            exr::ExrHandler, farbfeld::FarbfeldHandler, gif::GifHandler, ico::IcoHandler,
+            jpeg::JpegHandler, pdf::PdfHandler, png::PngHandler, pnm::PnmHandler, qoi::QoiHandler,
+            tga::TgaHandler, tiff::TiffHandler, webp::WebpHandler, EncodeOptions, Encoded,
+            ImfconvHandler,
+        },
+        montage::Montage,
+        sprite_sheet::{sprite_map, SpriteSheet},
+    },
+    progress::ProgressFn,
+    reader::{
+        decode, is_svg, orient, probe, rasterize_svg, read_animation, read_exif, read_file,
+        read_icc_profile, read_iptc, read_orientation, read_pages, read_xmp, FileData,
     },
-    reader::read_image,
 };
 
+#[cfg(feature = "jxl")]
+use self::handler::format::jxl::JxlHandler;
+#[cfg(feature = "http")]
+use self::reader::fetch;
+
+/// Destination path that stands for the standard output.
+const STDOUT_PATH: &str = "-";
+
+pub use self::batch::{BatchFailure, BatchReport, ImfconvBatch, Incremental, PlannedConversion};
+pub use self::handler::{
+    color_profile::ImfconvColorProfile,
+    composite::Layer,
+    transform::{PixelMap, Stamp, Transform},
+};
+pub use self::progress::{Progress, Stage};
+pub use self::sink::{FileSink, OutputSink};
+
+mod batch;
 mod handler;
+mod progress;
 mod reader;
+mod sink;
 
 /// The image conversion library.
 /// This struct can be used as method chains.
-/// ```
-/// let imfconv = Imfconv::new(src, dest).set_image_format(itype);
+/// ```no_run
+/// # use std::path::Path;
+/// # use imfconv::imfconv::{Imfconv, ImageType};
+/// # let (src, dest, itype) = (Path::new("in.jpg"), Path::new("out"), ImageType::PNG);
+/// let imfconv = Imfconv::new(src, dest).unwrap().set_image_format(&itype);
 /// ```
 pub struct Imfconv {
-    image: Vec<u8>,
-    w: u32,
-    h: u32,
+    /// Pixels of every image that is not decoded from its source when converting,
+    /// such as split pages and rasterized SVG documents.
+    images: Vec<Option<DynamicImage>>,
     format: Box<dyn ImfconvHandler>,
     color: Box<dyn ImfconvColorProfile>,
+    /// Operations applied to every image, in the order they were added.
+    transforms: Vec<Transform>,
+    /// Color space the pixels are converted to, `None` leaves them as they are.
+    color_space: Option<ColorSpace>,
+    /// Color transparent pixels are composited over for formats without alpha.
+    background: [u8; 3],
+    options: EncodeOptions,
+    /// Path of every image, empty for sources given as bytes.
+    source_paths: Vec<PathBuf>,
+    /// File contents of every image, which the metadata and the EXIF orientation are read from.
+    source_data: Vec<Arc<FileData>>,
+    auto_orient: bool,
     dest_path: PathBuf,
+    /// How the extension of the destination is matched to the output format.
+    output_extension: OutputExtension,
+    /// What happens to files that already exist at the destination.
+    overwrite: OverwritePolicy,
+    /// Destination the files are stored to.
+    sink: Box<dyn OutputSink>,
+    split_pages: bool,
+    /// Width and height of the tiles every image is split into, `None` writes it whole.
+    tile_size: Option<(u32, u32)>,
+    /// Grid all images are combined into, `None` keeps them apart.
+    montage: Option<Montage>,
+    /// Sheet all images are packed into, `None` keeps them apart.
+    sprite_sheet: Option<SpriteSheet>,
+    /// Placement of every source when they are stacked, `None` keeps them apart.
+    layers: Option<Vec<Layer>>,
+    /// Callback the stages of the conversion are reported to.
+    progress: Option<Arc<ProgressFn>>,
 }
 
 impl Imfconv {
     /// Making imfconv builder instance
+    ///
+    /// The output format is taken from the extension of the destination, e.g. WebP for
+    /// `out.webp`, and is PNG for unknown extensions. `set_image_format` overrides it.
+    ///
+    /// Only the header of the source is read here, returning an `Error` for unknown formats.
+    /// The pixels are decoded when converting, so corrupt image data fails the conversion.
     pub fn new(
         source_image_filepath: &Path,
         destination_filepath: &Path,
     ) -> Result<Self, Box<dyn Error>> {
-        let (w, h, i) = match read_image(source_image_filepath) {
-            Ok((w, h, i)) => (w, h, i),
-            Err(e) => return Err(e),
-        };
-        Ok(Self {
-            image: i,
-            w,
-            h,
+        Self::new_multi(
+            &[PathBuf::from(source_image_filepath)],
+            destination_filepath,
+        )
+    }
+
+    /// Making imfconv builder instance which writes every source as one page.
+    ///
+    /// Only multi-page formats such as TIFF accept more than one source,
+    /// `convert` returns an `Error` for the other formats unless the sources are combined
+    /// with `set_montage`, `set_sprite_sheet` or `set_layers`.
+    pub fn new_multi(
+        source_image_filepaths: &[PathBuf],
+        destination_filepath: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        if source_image_filepaths.is_empty() {
+            return Err("no source image was given".into());
+        }
+        let mut source_data = Vec::with_capacity(source_image_filepaths.len());
+        for src in source_image_filepaths {
+            source_data.push(Arc::new(read_file(src)?));
+        }
+        Self::from_sources(
+            source_image_filepaths.to_vec(),
+            source_data,
+            destination_filepath,
+        )
+    }
+
+    /// Making imfconv builder instance from the contents of an image file held in memory,
+    /// e.g. a request body or a database blob.
+    ///
+    /// The format is recognized from the contents. Camera RAW files and compressed SVG
+    /// documents are only recognized by their file extension, they have to be read with `new`.
+    pub fn from_bytes(
+        source_image_data: &[u8],
+        destination_filepath: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::from_sources(
+            vec![PathBuf::new()],
+            vec![Arc::new(FileData::Read(source_image_data.to_vec()))],
+            destination_filepath,
+        )
+    }
+
+    /// Making imfconv builder instance from an image file read from `source`, e.g. a socket,
+    /// a decompressor or an entry of an archive.
+    ///
+    /// The stream is read to its end before decoding, see `from_bytes` for the formats
+    /// that are recognized.
+    pub fn from_reader<R: Read>(
+        mut source: R,
+        destination_filepath: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut data = Vec::new();
+        if let Err(e) = source.read_to_end(&mut data) {
+            return Err(Box::new(e));
+        }
+        Self::from_bytes(&data, destination_filepath)
+    }
+
+    /// Making imfconv builder instance from an image downloaded from the HTTP or HTTPS `url`.
+    ///
+    /// The format is recognized from the contents, then from the `Content-Type` of the
+    /// response and finally from the extension in the URL.
+    /// Returns an `Error` if the request fails or the server responds with an error status.
+    #[cfg(feature = "http")]
+    pub fn from_url(url: &str, destination_filepath: &Path) -> Result<Self, Box<dyn Error>> {
+        let (src, data) = fetch(url)?;
+        Self::from_sources(
+            vec![src],
+            vec![Arc::new(FileData::Read(data))],
+            destination_filepath,
+        )
+    }
+
+    /// Making imfconv builder instance from an image file read from the standard input,
+    /// e.g. `cat photo.jpg | app`. Give `-` as `destination_filepath` to write the output
+    /// to the standard output as well.
+    pub fn from_stdin(destination_filepath: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_reader(io::stdin().lock(), destination_filepath)
+    }
+
+    /// Making imfconv builder instance from the file contents `source_data` of the sources
+    /// located at `source_paths`.
+    fn from_sources(
+        source_paths: Vec<PathBuf>,
+        source_data: Vec<Arc<FileData>>,
+        destination_filepath: &Path,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Only the headers are read here, the pixels are decoded by `convert`.
+        for (src, data) in source_paths.iter().zip(&source_data) {
+            probe(src, data)?;
+        }
+        let imfconv = Self {
+            images: vec![None; source_paths.len()],
             format: Box::new(PngHandler),
             color: Box::new(RgbColor),
+            transforms: Vec::new(),
+            color_space: None,
+            background: [255, 255, 255],
+            options: EncodeOptions::default(),
+            source_paths,
+            source_data,
+            auto_orient: true,
             dest_path: PathBuf::from(destination_filepath),
+            output_extension: OutputExtension::REPLACE,
+            overwrite: OverwritePolicy::OVERWRITE,
+            sink: Box::new(FileSink),
+            split_pages: false,
+            tile_size: None,
+            montage: None,
+            sprite_sheet: None,
+            layers: None,
+            progress: None,
+        };
+        Ok(match ImageType::from_path(destination_filepath) {
+            Some(image_type) => imfconv.set_image_format(&image_type),
+            None => imfconv,
         })
     }
 
+    /// Rasterize SVG sources to `width` x `height` pixels instead of their own size.
+    ///
+    /// The document is scaled to fill exactly that size. Other sources are left unchanged.
+    pub fn set_raster_size(mut self, width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        let sources = self.source_paths.iter().zip(&self.source_data);
+        for ((src, data), image) in sources.zip(self.images.iter_mut()) {
+            if is_svg(src, data) {
+                *image = Some(rasterize_svg(src, data, width, height)?);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Write every page of multi-page TIFF sources into its own file,
+    /// numbered `dest_001`, `dest_002` and so on.
+    ///
+    /// `pages` selects the pages to write starting from 1, e.g. `Some(2..=4)`,
+    /// and `None` writes every page. Other sources count as a single page.
+    pub fn set_split_pages(
+        mut self,
+        pages: Option<RangeInclusive<usize>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut images = Vec::new();
+        let mut source_paths = Vec::new();
+        let mut source_data = Vec::new();
+        for (src, data) in self.source_paths.iter().zip(&self.source_data) {
+            for (i, page) in read_pages(src, data)?.into_iter().enumerate() {
+                if pages.as_ref().is_none_or(|p| p.contains(&(i + 1))) {
+                    images.push(Some(page));
+                    source_paths.push(src.clone());
+                    source_data.push(data.clone());
+                }
+            }
+        }
+        if images.is_empty() {
+            return Err("no page of the source is in the selected range".into());
+        }
+        self.images = images;
+        self.source_paths = source_paths;
+        self.source_data = source_data;
+        self.split_pages = true;
+        Ok(self)
+    }
+
+    /// Combine all sources into a single contact sheet, e.g. for proofing, with `columns` cells
+    /// of `cell_width` x `cell_height` pixels per row and `gap` pixels between the cells.
+    ///
+    /// The sources are placed row by row in the order they are given to `new_multi`, after
+    /// their own operations. Larger images are scaled down to fit their cell, every image is
+    /// centered in its cell on the RGBA color `background`. Animated sources contribute their
+    /// first frame. Replaces `set_sprite_sheet` and `set_layers`.
+    /// Returns an `Error` if the number of columns or a size is zero.
+    pub fn set_montage(
+        mut self,
+        columns: u32,
+        cell_width: u32,
+        cell_height: u32,
+        gap: u32,
+        background: [u8; 4],
+    ) -> Result<Self, Box<dyn Error>> {
+        if columns == 0 || cell_width == 0 || cell_height == 0 {
+            return Err("the number of columns and the cell size must not be zero".into());
+        }
+        self.montage = Some(Montage {
+            columns,
+            cell_width,
+            cell_height,
+            gap,
+            background,
+        });
+        self.sprite_sheet = None;
+        self.layers = None;
+        Ok(self)
+    }
+
+    /// Pack all sources into a single transparent sprite sheet, `padding` pixels apart,
+    /// and write a JSON map of the sprites next to it, e.g. `dest.png` and `dest.json`.
+    ///
+    /// The map follows the hash layout of TexturePacker, every sprite is keyed by the file
+    /// name of its source and gives its position and size in the sheet. The sources are packed
+    /// after their own operations, animated sources contribute their first frame.
+    /// The sheet keeps its transparency with the `RGBA` color profile.
+    /// Replaces `set_montage` and `set_layers`.
+    /// Converting fails if the sheet is split into tiles.
+    pub fn set_sprite_sheet(mut self, padding: u32) -> Self {
+        self.sprite_sheet = Some(SpriteSheet { padding });
+        self.montage = None;
+        self.layers = None;
+        self
+    }
+
+    /// Stack all sources into a single image, the first source at the bottom, each placed,
+    /// faded and blended by its entry in `layers`.
+    ///
+    /// The canvas has the size of the first source after its own operations and layers are
+    /// clipped to it, animated sources contribute their first frame.
+    /// Replaces `set_montage` and `set_sprite_sheet`.
+    /// Returns an `Error` if there is not one layer for every source or an opacity is not
+    /// from 0.0 to 1.0.
+    pub fn set_layers(mut self, layers: &[Layer]) -> Result<Self, Box<dyn Error>> {
+        if layers.len() != self.source_data.len() {
+            return Err("there must be one layer for every source".into());
+        }
+        if layers.iter().any(|l| !(0.0..=1.0).contains(&l.opacity)) {
+            return Err("the layer opacity must be from 0.0 to 1.0".into());
+        }
+        self.layers = Some(layers.to_vec());
+        self.montage = None;
+        self.sprite_sheet = None;
+        Ok(self)
+    }
+
+    /// Split every image into a grid of `tile_width` x `tile_height` tiles and write each tile
+    /// into its own file, named by its column and row from 0, e.g. `dest_0_0` and `dest_1_0`.
+    /// The tiles in the last column and row are smaller if the size does not divide evenly.
+    ///
+    /// The tiles are cut after every other operation. Multiple images, e.g. split pages,
+    /// are numbered first, e.g. `dest_001_0_0`. Returns an `Error` if a size is zero.
+    /// Converting fails for animated sources.
+    pub fn tile(mut self, tile_width: u32, tile_height: u32) -> Result<Self, Box<dyn Error>> {
+        if tile_width == 0 || tile_height == 0 {
+            return Err("the tile size must not be zero".into());
+        }
+        self.tile_size = Some((tile_width, tile_height));
+        Ok(self)
+    }
+
     pub fn set_image_format(self, image_type: &ImageType) -> Self {
         let f: Box<dyn ImfconvHandler> = match image_type {
             ImageType::JPEG => Box::new(JpegHandler),
             ImageType::PNG => Box::new(PngHandler),
             ImageType::TIFF => Box::new(TiffHandler),
+            ImageType::WEBP => Box::new(WebpHandler),
+            ImageType::AVIF => Box::new(AvifHandler),
+            ImageType::BMP => Box::new(BmpHandler),
+            ImageType::GIF => Box::new(GifHandler),
+            ImageType::ICO => Box::new(IcoHandler),
+            ImageType::QOI => Box::new(QoiHandler),
+            ImageType::EXR => Box::new(ExrHandler),
+            ImageType::TGA => Box::new(TgaHandler),
+            ImageType::PNM => Box::new(PnmHandler),
+            ImageType::FARBFELD => Box::new(FarbfeldHandler),
+            ImageType::DDS => Box::new(DdsHandler),
+            ImageType::PDF => Box::new(PdfHandler),
+            ImageType::APNG => Box::new(ApngHandler),
+            #[cfg(feature = "jxl")]
+            ImageType::JXL => Box::new(JxlHandler),
         };
 
-        Self {
-            image: self.image,
-            w: self.w,
-            h: self.h,
-            format: f,
-            color: self.color,
-            dest_path: self.dest_path,
-        }
-    }
-
-    pub fn set_color_profile(self, color_profile: &ColorProfile) -> Result<Self, Box<dyn Error>> {
-        match color_profile {
-            ColorProfile::RGB => {
-                return Ok(Self {
-                    image: self.image,
-                    w: self.w,
-                    h: self.h,
-                    format: self.format,
-                    color: Box::new(RgbColor),
-                    dest_path: self.dest_path,
-                })
-            }
-            ColorProfile::GRAYSCALE => {
-                return Ok(Self {
-                    image: self.image,
-                    w: self.w,
-                    h: self.h,
-                    format: self.format,
-                    color: Box::new(Grayscale),
-                    dest_path: self.dest_path,
-                })
-            }
+        Self { format: f, ..self }
+    }
+
+    /// Choose what happens when the extension of the destination does not match the output
+    /// format, e.g. `out.png` written as JPEG. Defaults to `OutputExtension::REPLACE`.
+    ///
+    /// Extensions of the same format match, e.g. `.jpg` and `.jpeg`.
+    pub fn set_output_extension(mut self, output_extension: OutputExtension) -> Self {
+        self.output_extension = output_extension;
+        self
+    }
+
+    /// Choose what happens when a file already exists at the destination.
+    /// Defaults to `OverwritePolicy::OVERWRITE`.
+    ///
+    /// The policy applies to every file of the conversion, e.g. every tile.
+    pub fn set_overwrite_policy(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Store the files of `convert` to `sink` instead of writing them to the file system,
+    /// e.g. to upload them to an object storage.
+    ///
+    /// The paths handed to the sink are derived from the destination as for files.
+    pub fn set_output_sink(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Scale every image to exactly `width` x `height` pixels with `filter`.
+    ///
+    /// Like every geometric operation, the resize is applied after the EXIF orientation and
+    /// in the order the operations are added. Returns an `Error` if a size is zero.
+    pub fn resize(
+        self,
+        width: u32,
+        height: u32,
+        filter: ResizeFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.resize_with_mode(width, height, ResizeMode::FILL, filter)
+    }
+
+    /// Scale every image to `width` x `height` pixels with `filter`, framed by `mode`.
+    ///
+    /// `ResizeMode::FIT` scales the image to fit inside the size, `ResizeMode::FILL` stretches
+    /// it to exactly the size like `resize`, and `ResizeMode::COVER` scales it to cover the size
+    /// and crops the overflow around the center. Both `FIT` and `COVER` keep the aspect ratio
+    /// and scale small images up. Returns an `Error` if a size is zero.
+    pub fn resize_with_mode(
+        self,
+        width: u32,
+        height: u32,
+        mode: ResizeMode,
+        filter: ResizeFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Resize {
+            width,
+            height,
+            mode,
+            filter,
+        })
+    }
+
+    /// Scale every image down to fit inside `max_width` x `max_height` pixels without
+    /// distorting it. Images that already fit are left at their size.
+    ///
+    /// Returns an `Error` if a size is zero.
+    pub fn thumbnail(self, max_width: u32, max_height: u32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Thumbnail {
+            max_width,
+            max_height,
+        })
+    }
+
+    /// Cut out the `width` x `height` region whose top left corner is at `x`, `y`
+    /// of every image.
+    ///
+    /// Returns an `Error` if a size is zero. Converting fails if the region
+    /// exceeds the bounds of an image.
+    pub fn crop(self, x: u32, y: u32, width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Crop {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Center every image on a canvas of exactly `width` x `height` pixels of the RGBA
+    /// color `color`, e.g. for marketplaces that require square images.
+    ///
+    /// Larger images are scaled down to fit the canvas first, keeping their aspect ratio,
+    /// smaller images are not scaled up. A translucent color adds an alpha channel.
+    ///
+    /// Returns an `Error` if a size is zero.
+    pub fn pad_to(self, width: u32, height: u32, color: [u8; 4]) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Pad {
+            width,
+            height,
+            color,
+        })
+    }
+
+    /// Grow the canvas of every image by `top`, `right`, `bottom` and `left` pixels of the
+    /// RGBA color `color` without scaling the image, e.g. for borders or bleed.
+    /// A translucent color adds an alpha channel.
+    pub fn extend_canvas(
+        mut self,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        color: [u8; 4],
+    ) -> Self {
+        self.transforms.push(Transform::ExtendCanvas {
+            top,
+            right,
+            bottom,
+            left,
+            color,
+        });
+        self
+    }
+
+    /// Remove the uniform borders around every image, e.g. the margins of a scan.
+    ///
+    /// The color of the top left pixel, within a small tolerance, and fully transparent pixels
+    /// count as border. Images that are border only are kept as they are.
+    pub fn trim(mut self) -> Self {
+        self.transforms.push(Transform::Trim);
+        self
+    }
+
+    /// Rotate every image clockwise by `degrees`, counterclockwise for negative angles.
+    ///
+    /// Multiples of 90 degrees only move the pixels and lose nothing. Other angles interpolate
+    /// the pixels and enlarge the image to hold all of it, the uncovered corners are filled
+    /// with the RGBA color `fill`. A translucent fill adds an alpha channel.
+    ///
+    /// Returns an `Error` if `degrees` is not a finite number.
+    pub fn rotate(self, degrees: f64, fill: [u8; 4]) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Rotate { degrees, fill })
+    }
+
+    /// Mirror every image left to right.
+    pub fn flip_horizontal(mut self) -> Self {
+        self.transforms.push(Transform::FlipHorizontal);
+        self
+    }
+
+    /// Mirror every image top to bottom.
+    pub fn flip_vertical(mut self) -> Self {
+        self.transforms.push(Transform::FlipVertical);
+        self
+    }
+
+    /// Composite the image located at `path`, e.g. a logo, onto every image at `anchor`,
+    /// `margin` pixels away from the edges of the image.
+    ///
+    /// `opacity` from `0.0` to `1.0` scales the alpha channel of the watermark, parts of the
+    /// watermark outside of the image are cut off.
+    ///
+    /// Returns an `Error` if the opacity is outside of `0.0` to `1.0` or the watermark cannot
+    /// be read. It is decoded once, conversions replaying the operations of this one with
+    /// `push_transform` share it.
+    pub fn watermark(
+        self,
+        path: &Path,
+        anchor: Anchor,
+        margin: u32,
+        opacity: f32,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Watermark {
+            path: path.to_path_buf(),
+            anchor,
+            margin,
+            opacity,
+            stamp: Stamp::default(),
+        })
+    }
+
+    /// Correct the brightness and contrast of every image, e.g. of dark scans.
+    ///
+    /// `brightness` from `-255` to `255` is added to the color channels in 8 bit steps,
+    /// `0` keeps the brightness. `contrast` scales the distance of the channels from mid gray,
+    /// `1.0` keeps the contrast, smaller values flatten and larger values strengthen it.
+    ///
+    /// Returns an `Error` if the contrast is negative.
+    pub fn adjust(self, brightness: i32, contrast: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Adjust {
+            brightness,
+            contrast,
+        })
+    }
+
+    /// Call `map` on the RGBA samples of every pixel of every image, for math the other
+    /// operations do not cover, e.g. `|p| p.swap(0, 2)` to swap the red and blue channels.
+    ///
+    /// The samples are 8 bit sRGB encoded, deeper images are reduced to 8 bits.
+    /// An alpha channel is added if `map` makes a pixel translucent.
+    /// The operation list cannot be serialized with this operation in it.
+    pub fn map_pixels<F: Fn(&mut [u8; 4]) + Send + Sync + 'static>(mut self, map: F) -> Self {
+        let map = PixelMap::new(map);
+        self.transforms.push(Transform::MapPixels(map));
+        self
+    }
+
+    /// Rotate the hue of every image by `degrees` around the HSL color wheel,
+    /// e.g. `120.0` turns red into green.
+    ///
+    /// Returns an `Error` if `degrees` is not a finite number.
+    pub fn rotate_hue(self, degrees: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::HueSaturation {
+            hue: degrees,
+            saturation: 1.0,
+        })
+    }
+
+    /// Scale the HSL saturation of every image by `factor`. `0.0` removes the colors,
+    /// `1.0` keeps them and larger values make them more vivid.
+    ///
+    /// Returns an `Error` if the factor is negative.
+    pub fn saturate(self, factor: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::HueSaturation {
+            hue: 0.0,
+            saturation: factor,
+        })
+    }
+
+    /// Blur every image with a Gaussian of the standard deviation `sigma` in pixels,
+    /// larger values blur more.
+    ///
+    /// Returns an `Error` if `sigma` is not positive.
+    pub fn blur(self, sigma: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Blur { sigma })
+    }
+
+    /// Sharpen every image with an unsharp mask, e.g. after scaling it down.
+    ///
+    /// `sigma` is the standard deviation in pixels of the blur that separates the detail,
+    /// `amount` scales how much of the detail is added, e.g. `0.5` for a slight sharpening.
+    ///
+    /// Returns an `Error` if `sigma` is not positive or `amount` is negative.
+    pub fn sharpen(self, sigma: f32, amount: f32) -> Result<Self, Box<dyn Error>> {
+        self.unsharp_mask(amount, sigma, 0)
+    }
+
+    /// Sharpen every image with an unsharp mask that leaves low contrast detail alone,
+    /// e.g. for print output where sharpening smooth areas brings out noise and halos.
+    ///
+    /// `radius` is the standard deviation in pixels of the blur that separates the detail and
+    /// `amount` scales how much of the detail is added. Pixels that differ from the blurred
+    /// image by at most `threshold` in 8 bit steps in every channel are not sharpened,
+    /// `0` sharpens everything like `sharpen`.
+    ///
+    /// Returns an `Error` if `radius` is not positive or `amount` is negative.
+    pub fn unsharp_mask(
+        self,
+        amount: f32,
+        radius: f32,
+        threshold: u8,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Sharpen {
+            sigma: radius,
+            amount,
+            threshold: threshold as f32 / 255.0,
+        })
+    }
+
+    /// Reduce the noise of every image with `filter`, e.g. of high ISO photos before they are
+    /// compressed. `radius` is the distance in pixels of the neighbours that are mixed,
+    /// `1` or `2` for a light denoising.
+    ///
+    /// Returns an `Error` if the radius is zero.
+    pub fn denoise(self, filter: Denoise, radius: u32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Denoise { filter, radius })
+    }
+
+    /// Darken every image towards its corners, e.g. for stylized exports.
+    ///
+    /// `strength` from `0.0` to `1.0` is how much the corners are darkened, `1.0` turns them
+    /// black. The darkening starts at `radius` from `0.0` to `1.0` of the way from the center
+    /// to the corners and rises smoothly from there.
+    ///
+    /// Returns an `Error` if a parameter is outside of `0.0` to `1.0`.
+    pub fn vignette(self, strength: f32, radius: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Vignette { strength, radius })
+    }
+
+    /// Stamp the single line `text` onto every image at `anchor`, `margin` pixels away from
+    /// the edges of the image, e.g. an order ID or a copyright line.
+    ///
+    /// `font` names the font family installed on the system, with fallback families separated
+    /// by commas, e.g. `"DejaVu Sans, sans-serif"`. `size` is the font size in pixels and
+    /// `color` the RGBA color of the glyphs.
+    ///
+    /// Returns an `Error` if the size is not positive or no font can render the text.
+    /// It is rendered once and stamped onto every image.
+    pub fn text(
+        self,
+        text: &str,
+        font: &str,
+        size: f32,
+        color: [u8; 4],
+        anchor: Anchor,
+        margin: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Text {
+            text: text.to_string(),
+            font: font.to_string(),
+            size,
+            color,
+            anchor,
+            margin,
+            stamp: Stamp::default(),
+        })
+    }
+
+    /// Add `transform` after the operations added so far, e.g. to replay the list returned
+    /// by `transforms` on another conversion.
+    ///
+    /// Returns an `Error` if the parameters of `transform` are invalid, or its watermark or
+    /// text cannot be prepared.
+    pub fn push_transform(mut self, transform: Transform) -> Result<Self, Box<dyn Error>> {
+        transform.validate()?;
+        transform.prepare()?;
+        self.transforms.push(transform);
+        Ok(self)
+    }
+
+    /// The operations applied to every image, in the order they were added.
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    /// Remove every metadata block, such as EXIF, XMP, IPTC and ICC profiles, from the output.
+    /// Defaults to `false`.
+    ///
+    /// Only the pixels of the source are converted, so metadata reaches the output only
+    /// through the options that copy or set it. Stripping overrides all of those options,
+    /// which makes it safe to publish the output regardless of how they are configured.
+    pub fn set_strip_metadata(mut self, strip: bool) -> Self {
+        self.options.strip_metadata = strip;
+        self
+    }
+
+    /// Copy the EXIF metadata of the source, such as the camera model and the capture time,
+    /// to the output. Defaults to `false`.
+    ///
+    /// The metadata of the first source is used. It is written to JPEG, PNG, APNG, WebP and
+    /// TIFF output, the other formats have no place for it.
+    pub fn set_copy_exif(mut self, copy: bool) -> Self {
+        self.options.metadata.exif = match copy {
+            true => read_exif(&self.source_data[0]),
+            false => None,
+        };
+        if self.auto_orient {
+            self.options.metadata.set_exif_orientation(1);
+        }
+        self
+    }
+
+    /// Rotate and flip the pixels as the EXIF orientation of the source specifies,
+    /// e.g. for photos taken with a phone held upright. Defaults to `true`.
+    ///
+    /// The orientation of copied EXIF metadata is reset to upright, so viewers do not
+    /// rotate the pixels a second time. Without auto orientation the pixels and the
+    /// copied orientation are left as they are.
+    ///
+    /// HEIF sources are always upright, as their transformations are applied when they are
+    /// decoded and their EXIF orientation is ignored.
+    pub fn set_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        let orientation = match auto_orient {
+            true => 1,
+            false => read_orientation(&self.source_data[0]),
+        };
+        self.options.metadata.set_exif_orientation(orientation);
+        self
+    }
+
+    /// Copy the XMP packet of the source, such as keywords and ratings, to the output.
+    /// Defaults to `false`.
+    ///
+    /// The packet of the first source is used, independent of `set_copy_exif`.
+    /// JPEG, PNG, WebP and TIFF sources are read. It is written to JPEG, PNG, APNG, WebP and
+    /// TIFF output, the other formats have no place for it.
+    pub fn set_copy_xmp(mut self, copy: bool) -> Self {
+        self.options.metadata.xmp = match copy {
+            true => read_xmp(&self.source_data[0]),
+            false => None,
+        };
+        self
+    }
+
+    /// Copy the IPTC-IIM records of the source, such as captions and credits, to the output.
+    /// Defaults to `false`.
+    ///
+    /// The records of the first source are used, independent of `set_copy_exif`.
+    /// Only JPEG and TIFF files store IPTC records, both as source and as output.
+    pub fn set_copy_iptc(mut self, copy: bool) -> Self {
+        self.options.metadata.iptc = match copy {
+            true => read_iptc(&self.source_data[0]),
+            false => None,
+        };
+        self
+    }
+
+    /// Embed the ICC profile `profile` in the output, so color managed viewers
+    /// interpret the pixels in its color space.
+    ///
+    /// The pixels are not converted, the profile has to describe the color space they are in.
+    /// It is written to JPEG, PNG, APNG, WebP and TIFF output, the other formats have no place
+    /// for it. Returns an `Error` if `profile` is not an ICC profile.
+    pub fn set_icc_profile(mut self, profile: &[u8]) -> Result<Self, Box<dyn Error>> {
+        // Every profile has a 128 byte header with the "acsp" signature.
+        if profile.len() < 128 || &profile[36..40] != b"acsp" {
+            return Err("the data is not an ICC profile".into());
         }
+        self.options.metadata.icc_profile = Some(profile.to_vec());
+        Ok(self)
+    }
+
+    /// Embed the ICC profile stored in the file at `path` in the output,
+    /// see `set_icc_profile`.
+    pub fn set_icc_profile_file(self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        match fs::read(path) {
+            Ok(p) => self.set_icc_profile(&p),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Convert the pixels to `color_space`, e.g. to deliver Adobe RGB files for print.
+    /// By default the pixels stay in the color space of the source.
+    ///
+    /// The ICC profile embedded in JPEG, PNG, WebP and TIFF sources is honored,
+    /// sources without one are taken as sRGB. The output is tagged with the profile
+    /// of `color_space` like `set_icc_profile` does, which replaces a profile set before.
+    pub fn set_color_space(mut self, color_space: &ColorSpace) -> Result<Self, Box<dyn Error>> {
+        self.options.metadata.icc_profile = Some(icc_profile(color_space).encode()?);
+        self.color_space = Some(*color_space);
+        Ok(self)
+    }
+
+    /// Composite transparent images over the RGB color `r`, `g`, `b` when the output format
+    /// has no alpha channel, e.g. for JPEG output of a transparent PNG. Defaults to white.
+    ///
+    /// PNG, APNG, WebP, TIFF, QOI, TGA, ICO, DDS and farbfeld output keep the transparency
+    /// with the `RGBA` and `GRAYSCALE_ALPHA` color profiles. The other profiles drop it and
+    /// composite over the background color as well.
+    pub fn set_background_color(mut self, r: u8, g: u8, b: u8) -> Self {
+        self.background = [r, g, b];
+        self
+    }
+
+    /// Correct the gamma of every image, so that each color sample from `0.0` to `1.0`
+    /// becomes `sample^(1 / gamma)`. Like the other operations it is applied in the order
+    /// it is added, `1.0` changes nothing.
+    ///
+    /// `2.2` encodes linear data, e.g. from a camera feed, for display, `1.0 / 2.2` decodes it.
+    ///
+    /// Returns an `Error` if the gamma is not positive.
+    pub fn set_gamma(self, gamma: f32) -> Result<Self, Box<dyn Error>> {
+        self.push_transform(Transform::Gamma { gamma })
+    }
+
+    /// Declare the physical resolution of the output in dots per inch, e.g. 300 for print.
+    /// By default no resolution is declared.
+    ///
+    /// It is written to JPEG, PNG, APNG, TIFF and BMP output. PDF pages are sized to print
+    /// the image at this resolution, instead of one pixel per point (72 dpi).
+    pub fn set_dpi(mut self, dpi: u32) -> Self {
+        self.options.dpi = Some(dpi.max(1));
+        self
+    }
+
+    /// Bits per sample of PNG and TIFF output. Defaults to `BitDepth::EIGHT`.
+    ///
+    /// 16 bit sources keep their precision through the color profiles,
+    /// other formats are always written with 8 bits.
+    pub fn set_bit_depth(mut self, bit_depth: BitDepth) -> Self {
+        self.options.bit_depth = bit_depth;
+        self
+    }
+
+    /// Dithering of GIF and indexed PNG output. Defaults to `Dither::NONE`.
+    ///
+    /// Dithering only applies when the image has more colors than fit into the palette.
+    pub fn set_dither(mut self, dither: Dither) -> Self {
+        self.options.dither = dither;
+        self
+    }
+
+    /// Quality of JPEG output, from 1 (smallest) to 100 (best). Defaults to 75.
+    ///
+    /// The quality also applies to images embedded in PDF output with `PdfCompression::JPEG`.
+    pub fn set_jpeg_quality(mut self, quality: u8) -> Self {
+        self.options.jpeg_quality = quality;
+        self
+    }
+
+    /// Encode JPEG output progressively. Defaults to `false`.
+    ///
+    /// Progressive files are refined over several scans, so browsers can show a coarse
+    /// preview while the rest is still loading.
+    pub fn set_jpeg_progressive(mut self, progressive: bool) -> Self {
+        self.options.jpeg_progressive = progressive;
+        self
+    }
+
+    /// Largest size of JPEG and lossy WebP output in bytes.
+    ///
+    /// The quality is lowered from the configured one until the output fits,
+    /// converting fails if it is still too large at the lowest quality.
+    /// Lossless WebP output is not affected.
+    pub fn set_target_size_bytes(mut self, target_size: usize) -> Self {
+        self.options.target_size_bytes = Some(target_size);
+        self
+    }
+
+    /// Optimize the Huffman tables of JPEG output. Defaults to `false`.
+    ///
+    /// The tables are built from an extra pass over the image, which makes files smaller
+    /// without any loss in quality.
+    pub fn set_jpeg_optimize(mut self, optimize: bool) -> Self {
+        self.options.jpeg_optimize = optimize;
+        self
+    }
+
+    /// Chroma subsampling of JPEG output. Defaults to `ChromaSubsampling::S444`.
+    ///
+    /// Subsampling makes photos smaller, but blurs colored edges such as text in screenshots.
+    pub fn set_jpeg_subsampling(mut self, subsampling: ChromaSubsampling) -> Self {
+        self.options.jpeg_subsampling = subsampling;
+        self
+    }
+
+    /// Compression level of PNG and APNG output. Defaults to `PngCompression::FAST`.
+    pub fn set_png_compression(mut self, compression: PngCompression) -> Self {
+        self.options.png_compression = compression;
+        self
+    }
+
+    /// Row filter of PNG and APNG output. Defaults to `PngFilter::ADAPTIVE`.
+    pub fn set_png_filter(mut self, filter: PngFilter) -> Self {
+        self.options.png_filter = filter;
+        self
+    }
+
+    /// Write PNG output as an indexed image with at most `colors` colors (2 to 256).
+    ///
+    /// Images with more colors are quantized, which usually makes icons and
+    /// graphics much smaller. The bit depth setting does not apply to indexed output.
+    /// Translucent colors keep their alpha in the palette.
+    ///
+    /// Returns an `Error` if `colors` is not between 2 and 256.
+    pub fn set_png_palette(mut self, colors: usize) -> Result<Self, Box<dyn Error>> {
+        if !(2..=256).contains(&colors) {
+            return Err(format!("the palette size {} is not between 2 and 256", colors).into());
+        }
+        self.options.png_palette_colors = Some(colors);
+        Ok(self)
+    }
+
+    /// Interlace PNG output with Adam7. Defaults to `false`.
+    ///
+    /// Interlaced images can be shown at a low resolution before they are fully loaded,
+    /// at the cost of slightly larger files. APNG output is never interlaced.
+    pub fn set_png_interlace(mut self, interlace: bool) -> Self {
+        self.options.png_interlace = interlace;
+        self
+    }
+
+    /// Compression of TIFF output. Defaults to `TiffCompression::NONE`.
+    pub fn set_tiff_compression(mut self, compression: TiffCompression) -> Self {
+        self.options.tiff_compression = compression;
+        self
+    }
+
+    /// Encode WebP output losslessly instead of using the lossy quality setting.
+    /// Defaults to `false`.
+    ///
+    /// The quality is kept while lossless encoding is enabled and applies again once it is
+    /// disabled, so both settings can be chosen independently and in any order.
+    pub fn set_webp_lossless(mut self, lossless: bool) -> Self {
+        self.options.webp_lossless = lossless;
+        self
+    }
+
+    /// Quality of lossy WebP output, from 0 (smallest) to 100 (best). Defaults to 80.
+    ///
+    /// This does not switch lossless encoding off, see `set_webp_lossless`.
+    pub fn set_webp_quality(mut self, quality: u8) -> Self {
+        self.options.webp_quality = quality;
+        self
+    }
+
+    /// Quality of AVIF output, from 1 (smallest) to 100 (best). Defaults to 80.
+    pub fn set_avif_quality(mut self, quality: u8) -> Self {
+        self.options.avif_quality = quality;
+        self
+    }
+
+    /// AVIF encoder speed, from 1 (slowest, smallest files) to 10 (fastest). Defaults to 4.
+    ///
+    /// Encoding time grows quickly at low speeds, especially for large images.
+    pub fn set_avif_speed(mut self, speed: u8) -> Self {
+        self.options.avif_speed = speed;
+        self
+    }
+
+    /// Sizes of the icons packed into ICO output. Defaults to 16, 32, 48 and 256.
+    ///
+    /// The source is resized for each size and centered on a transparent square,
+    /// so every size must be between 1 and 256.
+    pub fn set_ico_sizes(mut self, sizes: &[u32]) -> Result<Self, Box<dyn Error>> {
+        if sizes.is_empty() {
+            return Err("at least one icon size is required".into());
+        }
+        if let Some(size) = sizes.iter().find(|s| !(1..=256).contains(*s)) {
+            return Err(format!("the icon size {} is not between 1 and 256", size).into());
+        }
+        self.options.ico_sizes = sizes.to_vec();
+        Ok(self)
+    }
+
+    /// Encode JPEG XL output losslessly. Defaults to `true`.
+    ///
+    /// Lossy JPEG XL is encoded by libjxl and needs the `jxl-lossy` feature, without it
+    /// converting with `false` returns an error.
+    #[cfg(feature = "jxl")]
+    pub fn set_jxl_lossless(mut self, lossless: bool) -> Self {
+        self.options.jxl_lossless = lossless;
+        self
+    }
+
+    /// Quality of lossy JPEG XL output, from 0 (smallest) to 100 (best). Defaults to 90,
+    /// which libjxl considers visually lossless.
+    ///
+    /// This does not switch lossless encoding off, see `set_jxl_lossless`.
+    #[cfg(feature = "jxl")]
+    pub fn set_jxl_quality(mut self, quality: u8) -> Self {
+        self.options.jxl_quality = quality;
+        self
+    }
+
+    /// Sample encoding of PNM output. Defaults to `PnmEncoding::BINARY` (P5/P6).
+    pub fn set_pnm_encoding(mut self, encoding: PnmEncoding) -> Self {
+        self.options.pnm_encoding = encoding;
+        self
+    }
+
+    /// Block compression of DDS output. Defaults to `DdsCompression::NONE`.
+    pub fn set_dds_compression(mut self, compression: DdsCompression) -> Self {
+        self.options.dds_compression = compression;
+        self
+    }
+
+    /// Compression of the image embedded in PDF output. Defaults to `PdfCompression::FLATE`.
+    pub fn set_pdf_compression(mut self, compression: PdfCompression) -> Self {
+        self.options.pdf_compression = compression;
+        self
+    }
+
+    /// Time every frame of animated output is shown, in milliseconds.
+    ///
+    /// Animated sources keep their own delays unless this is set,
+    /// frames written from several sources are shown for 100 ms.
+    pub fn set_frame_delay(mut self, delay_ms: u32) -> Self {
+        self.options.frame_delay_ms = Some(delay_ms);
+        self
+    }
+
+    /// Number of times animated output is played, 0 repeats it forever.
+    ///
+    /// Animated sources keep their own loop count unless this is set,
+    /// frames written from several sources repeat forever.
+    pub fn set_loop_count(mut self, loop_count: u16) -> Self {
+        self.options.loop_count = Some(loop_count);
+        self
+    }
+
+    pub fn set_color_profile(
+        mut self,
+        color_profile: &ColorProfile,
+    ) -> Result<Self, Box<dyn Error>> {
+        self.color = match color_profile {
+            ColorProfile::RGB => Box::new(RgbColor),
+            ColorProfile::RGBA => Box::new(RgbaColor),
+            ColorProfile::GRAYSCALE => Box::new(Grayscale),
+            ColorProfile::GRAYSCALE_ALPHA => Box::new(GrayscaleAlpha),
+            ColorProfile::CMYK => Box::new(CmykColor),
+            ColorProfile::SEPIA => Box::new(Sepia),
+            ColorProfile::INVERT => Box::new(Invert),
+            ColorProfile::TONE_MAP(operator) => Box::new(ToneMap {
+                operator: *operator,
+            }),
+            ColorProfile::DUOTONE(shadow, highlight) => Box::new(Duotone {
+                shadow: *shadow,
+                highlight: *highlight,
+            }),
+            ColorProfile::POSTERIZE(levels) => Box::new(Posterize { levels: *levels }),
+            ColorProfile::BILEVEL(threshold) => Box::new(Bilevel {
+                threshold: *threshold,
+            }),
+        };
+        self.options.cmyk = matches!(color_profile, ColorProfile::CMYK);
+        self.options.bilevel = matches!(color_profile, ColorProfile::BILEVEL(_));
+        Ok(self)
+    }
+
+    /// Convert the pixels with `color_profile` instead of one of the built in profiles,
+    /// e.g. to apply a lookup table of your own.
+    pub fn set_custom_color_profile(mut self, color_profile: Box<dyn ImfconvColorProfile>) -> Self {
+        self.color = color_profile;
+        self.options.cmyk = false;
+        self.options.bilevel = false;
+        self
+    }
+
+    /// Call `callback` at the start of every stage of the conversion, e.g. to show a
+    /// progress bar. `DECODING` and `TRANSFORMING` are reported for every source.
+    pub fn set_progress_callback<F: Fn(Progress) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
     }
 
     /// Execute the builder.
     ///
     /// Returns `()` if the conversion was successful. But returns an `Error` on failure.
+    /// The destination `-` writes the output to the standard output, which fails if the
+    /// conversion would produce more than one file.
     pub fn convert(&self) -> Result<(), Box<dyn Error>> {
-        let image_with_profile = match self.color.edit(self.w, self.h, &self.image) {
-            Ok(b) => b,
-            Err(e) => return Err(e),
+        if self.dest_path == Path::new(STDOUT_PATH) {
+            return self.convert_to_writer(io::stdout().lock());
+        }
+        self.write().map(|_| ())
+    }
+
+    /// Convert the image and store the files to the sink, returning what was written.
+    pub(crate) fn write(&self) -> Result<Written, Box<dyn Error>> {
+        let encoding = self.encode()?;
+        let encoded = encoding.files;
+        self.check_extension(encoded[0].1.extension)?;
+        let stem = match self.dest_path.file_stem() {
+            Some(s) => s.to_string_lossy().into_owned(),
+            None => String::new(),
+        };
+        let paths: Vec<PathBuf> = encoded
+            .iter()
+            .map(|(suffix, e)| {
+                let dest_path = match suffix.is_empty() {
+                    true => self.dest_path.clone(),
+                    false => self.dest_path.with_file_name(format!("{}{}", stem, suffix)),
+                };
+                self.output_path(&dest_path, e.extension)
+            })
+            .collect();
+        // Fail before any file is written, so no output is left incomplete.
+        if self.overwrite == OverwritePolicy::ERROR {
+            if let Some(path) = paths.iter().find(|p| self.sink.exists(p)) {
+                return Err(format!("the destination {} already exists", path.display()).into());
+            }
+        }
+        let mut files = Vec::with_capacity(paths.len());
+        for ((_, e), path) in encoded.iter().zip(paths) {
+            let path = match self.overwrite {
+                OverwritePolicy::SKIP if self.sink.exists(&path) => continue,
+                OverwritePolicy::RENAME => free_path(path, self.sink.as_ref()),
+                _ => path,
+            };
+            self.sink.write(&path, &e.data, e.mime_type)?;
+            files.push((path, e.data.len()));
+        }
+        self.report(0, Stage::DONE);
+        Ok(Written {
+            files,
+            width: encoding.width,
+            height: encoding.height,
+        })
+    }
+
+    /// Convert the image and return it as a `data:` URI with base64 encoded contents,
+    /// e.g. `data:image/png;base64,...`.
+    ///
+    /// Fails if the conversion would produce more than one file.
+    pub fn convert_to_data_uri(&self) -> Result<String, Box<dyn Error>> {
+        let encoded = self.encode_single("a data URI")?;
+        self.report(0, Stage::DONE);
+        Ok(format!(
+            "data:{};base64,{}",
+            encoded.mime_type,
+            STANDARD.encode(&encoded.data)
+        ))
+    }
+
+    /// Convert the image and return the contents of the encoded file, for conversions
+    /// entirely in memory.
+    ///
+    /// The destination path is not used, so an empty path such as `Path::new("")` may be
+    /// given to the constructor. Fails if the conversion would produce more than one file.
+    pub fn convert_to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let encoded = self.encode_single("a byte buffer")?;
+        self.report(0, Stage::DONE);
+        Ok(encoded.data)
+    }
+
+    /// Convert the image and write the encoded file to `writer`, e.g. the body of
+    /// an HTTP response or an entry of a zip archive, instead of the destination path.
+    ///
+    /// The file is encoded in memory first, nothing is written if the conversion fails.
+    /// Fails if the conversion would produce more than one file.
+    pub fn convert_to_writer<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let encoded = self.encode_single("a writer")?;
+        if let Err(e) = writer.write_all(&encoded.data).and_then(|_| writer.flush()) {
+            return Err(Box::new(e));
+        }
+        self.report(0, Stage::DONE);
+        Ok(())
+    }
+
+    /// Encode the images as a single file for `target`, which names it in the error
+    /// returned if the conversion would produce more than one file.
+    fn encode_single(&self, target: &str) -> Result<Encoded, Box<dyn Error>> {
+        let mut encoded = self.encode()?.files;
+        match encoded.len() {
+            1 => Ok(encoded.remove(0).1),
+            _ => Err(format!("{} can only hold a single output image", target).into()),
+        }
+    }
+
+    /// The path the output is written to, after the checks of the settings that `convert`
+    /// makes, without decoding or writing anything, and whether the output would be skipped
+    /// because a file already exists at that path.
+    ///
+    /// Conversions writing several files, e.g. split pages, name them after this path.
+    /// PNM output of grayscale images is planned with the `ppm` extension of pixmaps.
+    pub(crate) fn planned_path(&self) -> Result<(PathBuf, bool), Box<dyn Error>> {
+        self.check_color()?;
+        self.check_extension(self.format.extension())?;
+        let path = self.output_file();
+        Ok(match self.overwrite {
+            OverwritePolicy::ERROR if self.sink.exists(&path) => {
+                return Err(format!("the destination {} already exists", path.display()).into())
+            }
+            OverwritePolicy::SKIP if self.sink.exists(&path) => (path, true),
+            OverwritePolicy::RENAME => (free_path(path, self.sink.as_ref()), false),
+            _ => (path, false),
+        })
+    }
+
+    /// The path the output is written to before applying the overwrite policy, see
+    /// `planned_path`.
+    pub(crate) fn output_file(&self) -> PathBuf {
+        self.output_path(&self.dest_path, self.format.extension())
+    }
+
+    /// Returns an `Error` if the color profile cannot be written in the output format.
+    fn check_color(&self) -> Result<(), Box<dyn Error>> {
+        match self.options.cmyk && !self.format.supports_cmyk() {
+            true => Err("CMYK images can only be written as JPEG or TIFF".into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns an `Error` if the destination must have the `extension` of the output
+    /// but does not.
+    fn check_extension(&self, extension: &str) -> Result<(), Box<dyn Error>> {
+        let matches = extension_matches(&self.dest_path, extension);
+        if self.output_extension == OutputExtension::ERROR && !matches {
+            return Err(format!(
+                "the destination {} does not have the extension of the output format, {}",
+                self.dest_path.display(),
+                extension
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Report `stage` of the `i`th source to the progress callback, if there is one.
+    fn report(&self, i: usize, stage: Stage) {
+        if let Some(progress) = &self.progress {
+            progress(Progress {
+                source: self.source_paths[i].clone(),
+                stage,
+                completed: (stage == Stage::DONE) as usize,
+                total: 1,
+            });
+        }
+    }
+
+    /// The path a file with `extension` is written to for `dest_path`.
+    fn output_path(&self, dest_path: &Path, extension: &str) -> PathBuf {
+        match self.output_extension {
+            OutputExtension::REPLACE => dest_path.with_extension(extension),
+            _ if extension_matches(dest_path, extension) => dest_path.to_path_buf(),
+            _ => {
+                let mut path = dest_path.as_os_str().to_os_string();
+                path.push(".");
+                path.push(extension);
+                PathBuf::from(path)
+            }
+        }
+    }
+
+    /// The EXIF orientation the `i`th image is turned upright from, 1 without auto orientation.
+    ///
+    /// It is read when the image is converted, not when the source is opened.
+    fn orientation(&self, i: usize) -> u16 {
+        match self.auto_orient {
+            true => read_orientation(&self.source_data[i]),
+            false => 1,
+        }
+    }
+
+    /// `image` with every geometric operation applied.
+    fn transform<'a>(
+        &self,
+        image: Cow<'a, DynamicImage>,
+    ) -> Result<Cow<'a, DynamicImage>, Box<dyn Error>> {
+        let mut image = image;
+        for transform in &self.transforms {
+            image = Cow::Owned(transform.apply(&image)?);
+        }
+        Ok(image)
+    }
+
+    /// `image` converted to the target color space from the one `source_profile` describes.
+    fn convert_color_space<'a>(
+        &self,
+        image: Cow<'a, DynamicImage>,
+        source_profile: Option<&[u8]>,
+    ) -> Result<Cow<'a, DynamicImage>, Box<dyn Error>> {
+        match &self.color_space {
+            Some(c) => Ok(Cow::Owned(convert(
+                &image,
+                source_profile,
+                &icc_profile(c),
+            )?)),
+            None => Ok(image),
+        }
+    }
+
+    /// `image` composited over the background color if the output format or the color profile
+    /// has no alpha channel.
+    fn flatten<'a>(&self, image: Cow<'a, DynamicImage>) -> Cow<'a, DynamicImage> {
+        let keeps_alpha = self.format.supports_alpha() && self.color.keeps_alpha();
+        match image.color().has_alpha() && !keeps_alpha {
+            true => Cow::Owned(flatten(&image, self.background)),
+            false => image,
+        }
+    }
+
+    /// The ICC profile of the `i`th source, if its pixels are converted to another color space.
+    fn source_icc_profile(&self, i: usize) -> Option<Vec<u8>> {
+        match self.color_space {
+            Some(_) => read_icc_profile(&self.source_data[i]),
+            None => None,
+        }
+    }
+
+    /// The JSON map of the `sprites` at `positions` in the sprite sheet `sheet`,
+    /// which is written as `encoded`.
+    fn sprite_map(
+        &self,
+        encoded: &Encoded,
+        sheet: &DynamicImage,
+        sprites: &[DynamicImage],
+        positions: &[(u32, u32)],
+    ) -> Encoded {
+        let image_name = self.output_path(&self.dest_path, encoded.extension);
+        let image_name = match image_name.file_name() {
+            Some(n) => n.to_string_lossy().into_owned(),
+            None => String::new(),
         };
-        match self
-            .format
-            .exec(self.w, self.h, &image_with_profile, &self.dest_path)
-        {
-            Ok(_) => return Ok(()),
-            Err(e) => return Err(e),
+        let names: Vec<String> = self
+            .source_paths
+            .iter()
+            .map(|p| match p.file_name() {
+                Some(n) => n.to_string_lossy().into_owned(),
+                None => p.to_string_lossy().into_owned(),
+            })
+            .collect();
+        Encoded {
+            data: sprite_map(&image_name, sheet, &names, sprites, positions).into_bytes(),
+            extension: "json",
+            mime_type: "application/json",
+        }
+    }
+
+    /// Encode the images in memory.
+    fn encode(&self) -> Result<Encoding, Box<dyn Error>> {
+        self.check_color()?;
+        let combined =
+            self.montage.is_some() || self.sprite_sheet.is_some() || self.layers.is_some();
+        // Animations are kept for a single source only, several sources become pages.
+        let single = matches!((&self.images[..], combined), ([None], false));
+        let animation = match single {
+            true => {
+                self.report(0, Stage::DECODING);
+                read_animation(&self.source_data[0])?
+            }
+            false => None,
+        };
+        if let Some(animation) = &animation {
+            if self.tile_size.is_some() {
+                return Err("animations cannot be split into tiles".into());
+            }
+            self.report(0, Stage::TRANSFORMING);
+            let source_profile = self.source_icc_profile(0);
+            let orientation = self.orientation(0);
+            let mut frames = Vec::with_capacity(animation.frames.len());
+            for frame in &animation.frames {
+                let image = self.transform(orient(&frame.image, orientation))?;
+                let image = self.convert_color_space(image, source_profile.as_deref())?;
+                let image = self.flatten(image);
+                frames.push(AnimationFrame {
+                    image: self.color.edit(&image)?,
+                    delay_ms: self.options.frame_delay_ms.unwrap_or(frame.delay_ms),
+                });
+            }
+            let animation_with_profile = Animation {
+                frames,
+                loop_count: self.options.loop_count.unwrap_or(animation.loop_count),
+            };
+            self.report(0, Stage::ENCODING);
+            let encoded = self
+                .format
+                .encode_animation(&animation_with_profile, &self.options)?;
+            let (width, height) = match animation_with_profile.frames.first() {
+                Some(f) => (f.image.width(), f.image.height()),
+                None => (0, 0),
+            };
+            return Ok(Encoding {
+                files: vec![(String::new(), encoded)],
+                width,
+                height,
+            });
+        }
+
+        let mut images = Vec::with_capacity(self.images.len());
+        for (i, image) in self.images.iter().enumerate() {
+            let decoded;
+            let image = match image {
+                Some(image) => image,
+                None => {
+                    // The single source was reported before looking for an animation.
+                    if !single {
+                        self.report(i, Stage::DECODING);
+                    }
+                    decoded = decode(&self.source_paths[i], &self.source_data[i])?;
+                    &decoded
+                }
+            };
+            self.report(i, Stage::TRANSFORMING);
+            let source_profile = self.source_icc_profile(i);
+            let image = self.transform(orient(image, self.orientation(i)))?;
+            let image = self.convert_color_space(image, source_profile.as_deref())?;
+            images.push(image.into_owned());
+        }
+        let tiled = self.tile_size.is_some();
+        if let Some(montage) = &self.montage {
+            images = vec![montage.compose(&images)?];
+        }
+        if let Some(layers) = &self.layers {
+            images = vec![composite(&images, layers)];
+        }
+        let mut sprites = None;
+        if let Some(sprite_sheet) = &self.sprite_sheet {
+            if tiled {
+                return Err("sprite sheets cannot be split into tiles".into());
+            }
+            let (sheet, positions) = sprite_sheet.pack(&images);
+            sprites = Some((std::mem::replace(&mut images, vec![sheet]), positions));
+        }
+        let mut images_with_profile = Vec::with_capacity(images.len());
+        for image in &images {
+            let image = self.flatten(Cow::Borrowed(image));
+            images_with_profile.push(self.color.edit(&image)?);
+        }
+        self.report(0, Stage::ENCODING);
+        let (width, height) = match images_with_profile.first() {
+            Some(i) => (i.width(), i.height()),
+            None => (0, 0),
         };
+        let numbered = self.split_pages || (tiled && images_with_profile.len() > 1);
+        if !numbered && !tiled {
+            let encoded = self
+                .format
+                .encode_pages(&images_with_profile, &self.options)?;
+            let mut outputs = vec![(String::new(), encoded)];
+            if let Some((sprites, positions)) = sprites {
+                let map = self.sprite_map(&outputs[0].1, &images[0], &sprites, &positions);
+                outputs.push((String::from(".json"), map));
+            }
+            return Ok(Encoding {
+                files: outputs,
+                width,
+                height,
+            });
+        }
+
+        let mut encoded = Vec::with_capacity(images_with_profile.len());
+        for (i, image) in images_with_profile.iter().enumerate() {
+            let page = match numbered {
+                true => format!("_{:03}", i + 1),
+                false => String::new(),
+            };
+            let (tile_width, tile_height) = match self.tile_size {
+                Some(s) => s,
+                None => {
+                    encoded.push((page, self.format.encode(image, &self.options)?));
+                    continue;
+                }
+            };
+            for column in 0..image.width().div_ceil(tile_width) {
+                for row in 0..image.height().div_ceil(tile_height) {
+                    let (x, y) = (column * tile_width, row * tile_height);
+                    let width = tile_width.min(image.width() - x);
+                    let height = tile_height.min(image.height() - y);
+                    let tile = image.crop_imm(x, y, width, height);
+                    encoded.push((
+                        format!("{}_{}_{}", page, column, row),
+                        self.format.encode(&tile, &self.options)?,
+                    ));
+                }
+            }
+        }
+        Ok(Encoding {
+            files: encoded,
+            width,
+            height,
+        })
     }
 }
 
+/// Files encoded by a conversion.
+struct Encoding {
+    /// Every output file with the suffix appended to the file name of the destination.
+    files: Vec<(String, Encoded)>,
+    /// Size of the converted image, of the first one if there are several.
+    width: u32,
+    height: u32,
+}
+
+/// Files written by a conversion, for the manifest of batches.
+pub(crate) struct Written {
+    /// Path and size in bytes of every file written.
+    pub files: Vec<(PathBuf, usize)>,
+    /// Size of the converted image, of the first one if there are several.
+    pub width: u32,
+    pub height: u32,
+}
+
 /// Image formats available in imfconv
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ImageType {
     JPEG,
     PNG,
     TIFF,
+    WEBP,
+    AVIF,
+    BMP,
+    GIF,
+    ICO,
+    QOI,
+    /// OpenEXR, written as linear 32 bit float RGB.
+    EXR,
+    TGA,
+    /// Netpbm, written as a graymap for grayscale images and as a pixmap otherwise.
+    PNM,
+    /// farbfeld, 16 bit RGBA.
+    FARBFELD,
+    /// DirectDraw Surface texture, uncompressed or block compressed.
+    DDS,
+    /// Single page PDF with the image covering the whole page.
+    PDF,
+    /// Animated PNG, written from animated sources or with one frame per source.
+    APNG,
+    /// JPEG XL, available with the `jxl` feature.
+    #[cfg(feature = "jxl")]
+    JXL,
+}
+
+impl ImageType {
+    /// The format files with the extension of `path` are written in, e.g. `JPEG` for
+    /// `photo.jpg`. Returns `None` if the extension is missing or unknown.
+    pub fn from_path(path: &Path) -> Option<ImageType> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "jpg" | "jpeg" | "jpe" | "jfif" => ImageType::JPEG,
+            "png" => ImageType::PNG,
+            "tif" | "tiff" => ImageType::TIFF,
+            "webp" => ImageType::WEBP,
+            "avif" => ImageType::AVIF,
+            "bmp" => ImageType::BMP,
+            "gif" => ImageType::GIF,
+            "ico" => ImageType::ICO,
+            "qoi" => ImageType::QOI,
+            "exr" => ImageType::EXR,
+            "tga" => ImageType::TGA,
+            "pnm" | "pbm" | "pgm" | "ppm" => ImageType::PNM,
+            "ff" => ImageType::FARBFELD,
+            "dds" => ImageType::DDS,
+            "pdf" => ImageType::PDF,
+            "apng" => ImageType::APNG,
+            #[cfg(feature = "jxl")]
+            "jxl" => ImageType::JXL,
+            _ => return None,
+        })
+    }
+}
+
+/// Whether `path` has `extension` or another extension of the same format.
+fn extension_matches(path: &Path, extension: &str) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(e) if e.eq_ignore_ascii_case(extension) => true,
+        Some(_) => {
+            let format = ImageType::from_path(&Path::new("_").with_extension(extension));
+            format.is_some() && ImageType::from_path(path) == format
+        }
+        None => false,
+    }
+}
+
+/// The first of `path`, `name (1).ext`, `name (2).ext` and so on that `sink` does not hold.
+fn free_path(path: PathBuf, sink: &dyn OutputSink) -> PathBuf {
+    let stem = match path.file_stem() {
+        Some(s) => s.to_string_lossy().into_owned(),
+        None => return path,
+    };
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    let mut candidate = path.clone();
+    let mut n = 1;
+    while sink.exists(&candidate) {
+        let name = match &extension {
+            Some(e) => format!("{} ({}).{}", stem, n, e),
+            None => format!("{} ({})", stem, n),
+        };
+        candidate = path.with_file_name(name);
+        n += 1;
+    }
+    candidate
+}
+
+/// What happens to files that already exist at the destination
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverwritePolicy {
+    /// Replace the existing file.
+    OVERWRITE,
+    /// Fail the conversion without writing any file.
+    ERROR,
+    /// Keep the existing file and do not write the new one.
+    SKIP,
+    /// Write the new file as `name (1).ext`, or the first such number that is free.
+    RENAME,
+}
+
+/// What happens when the extension of the destination does not match the output format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputExtension {
+    /// Replace the extension, e.g. `out.png` is written as `out.jpeg`. Matching extensions
+    /// are replaced as well.
+    REPLACE,
+    /// Append the extension, e.g. `out.png` is written as `out.png.jpeg` and `out.jpg`
+    /// is kept.
+    APPEND,
+    /// Fail the conversion without writing a file. Matching extensions are kept.
+    ERROR,
+}
+
+/// How a layer is mixed with the layers below it
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    NORMAL,
+    MULTIPLY,
+    SCREEN,
+    OVERLAY,
+    DARKEN,
+    LIGHTEN,
+    DIFFERENCE,
+    ADD,
+}
+
+/// Noise reduction filters
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Denoise {
+    /// The median of the neighbours, removes speckles.
+    MEDIAN,
+    /// The average of the neighbours of a similar color, keeps edges sharp.
+    BILATERAL,
+}
+
+/// How images are framed when resized to a size of another aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResizeMode {
+    /// Fit inside the size, one side may be shorter.
+    FIT,
+    /// Stretch to exactly the size.
+    FILL,
+    /// Cover the size and crop the overflow around the center.
+    COVER,
+}
+
+/// Interpolation filters used when images are resized
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResizeFilter {
+    /// The nearest pixel, fast and blocky, e.g. for pixel art.
+    NEAREST,
+    /// Linear interpolation between the neighbouring pixels.
+    BILINEAR,
+    /// Lanczos with a radius of 3, the sharpest result.
+    LANCZOS3,
+}
+
+/// Positions of a watermark on the image
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Anchor {
+    TOP_LEFT,
+    TOP_RIGHT,
+    BOTTOM_LEFT,
+    BOTTOM_RIGHT,
+    CENTER,
+}
+
+/// Bits per sample of PNG and TIFF output
+#[derive(Debug, Clone, Copy)]
+pub enum BitDepth {
+    /// 8 bits, the depth of every other output format.
+    EIGHT,
+    /// 16 bits, keeping the precision of 16 bit and float sources.
+    SIXTEEN,
+}
+
+/// Dithering methods used when colors are reduced to a palette
+#[derive(Debug, Clone, Copy)]
+pub enum Dither {
+    /// Map every pixel to the closest palette color, which shows banding in gradients.
+    NONE,
+    /// Ordered dithering with an 8x8 Bayer matrix, a regular pattern that stays stable
+    /// in animations.
+    ORDERED,
+    /// Floyd-Steinberg error diffusion, usually the most faithful result.
+    FLOYDSTEINBERG,
+}
+
+/// Chroma subsampling of JPEG output
+#[derive(Debug, Clone, Copy)]
+pub enum ChromaSubsampling {
+    /// 4:4:4, full color resolution.
+    S444,
+    /// 4:2:2, half the horizontal color resolution.
+    S422,
+    /// 4:2:0, half the horizontal and vertical color resolution.
+    S420,
+}
+
+/// Compression levels of PNG output
+#[derive(Debug, Clone, Copy)]
+pub enum PngCompression {
+    /// Fastest encoding with larger files.
+    FAST,
+    /// Balance between encoding time and size.
+    DEFAULT,
+    /// Smallest files, slowest encoding.
+    BEST,
+}
+
+/// Row filters of PNG output
+///
+/// Filters predict each byte from its neighbours so that the differences compress better.
+#[derive(Debug, Clone, Copy)]
+pub enum PngFilter {
+    /// Store the rows unchanged, usually best for few colors.
+    NONE,
+    /// Predict from the pixel to the left.
+    SUB,
+    /// Predict from the pixel above.
+    UP,
+    /// Predict from the average of the left and upper pixels.
+    AVG,
+    /// Predict from the left, upper or upper left pixel, whichever is closest.
+    PAETH,
+    /// Choose the filter for every row separately.
+    ADAPTIVE,
+}
+
+/// Compression of TIFF output, every method is lossless
+#[derive(Debug, Clone, Copy)]
+pub enum TiffCompression {
+    /// Uncompressed samples, readable by every TIFF reader.
+    NONE,
+    /// Lempel-Ziv-Welch, the most widely supported compression.
+    LZW,
+    /// zlib compression, usually smaller than LZW.
+    DEFLATE,
+    /// Run length encoding, only effective for large areas of the same color.
+    PACKBITS,
+}
+
+/// Sample encodings of Netpbm output
+#[derive(Debug, Clone, Copy)]
+pub enum PnmEncoding {
+    /// Binary samples, P5 for graymaps and P6 for pixmaps.
+    BINARY,
+    /// Plain text samples, P2 for graymaps and P3 for pixmaps.
+    ASCII,
+}
+
+/// Compression modes of DDS output
+#[derive(Debug, Clone, Copy)]
+pub enum DdsCompression {
+    /// Uncompressed 32 bit BGRA.
+    NONE,
+    /// BC1 (DXT1), 4 bits per pixel without alpha.
+    BC1,
+    /// BC3 (DXT5), 8 bits per pixel with interpolated alpha.
+    BC3,
+}
+
+/// Compression of the image embedded in PDF output
+#[derive(Debug, Clone, Copy)]
+pub enum PdfCompression {
+    /// Baseline JPEG, small but lossy.
+    JPEG,
+    /// Deflate compressed samples, lossless.
+    FLATE,
 }
 
 /// Color profiles available in imfconv
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ColorProfile {
+    /// RGB without the transparency of the source, which is composited over the background
+    /// color, see `set_background_color`.
     RGB,
+    /// RGB keeping the transparency of the source, written with its alpha channel as PNG,
+    /// APNG, WebP, TIFF, QOI, TGA and ICO. Other formats composite the image over the
+    /// background color, see `set_background_color`.
+    RGBA,
     GRAYSCALE,
+    /// Grayscale keeping the transparency of the source, written with its alpha channel
+    /// as PNG. The other formats with an alpha channel store it as RGBA, see `RGBA`.
+    #[allow(non_camel_case_types)]
+    GRAYSCALE_ALPHA,
+    /// CMYK inks for print, written as JPEG or TIFF only.
+    ///
+    /// The conversion does not use a color profile, embed the profile of the printing
+    /// process with `set_icc_profile` where the print vendor requires one.
+    CMYK,
+    /// Warm brown tone of aged photographs.
+    SEPIA,
+    /// Inverted colors, the positive of a scanned film negative.
+    INVERT,
+    /// Tone mapping of float HDR sources, such as OpenEXR and camera RAW files,
+    /// so bright highlights keep their detail in 8 bit output instead of clipping.
+    #[allow(non_camel_case_types)]
+    TONE_MAP(ToneMapping),
+    /// Two colors, the luminance of the source is mapped onto the gradient from the first,
+    /// shadow color to the second, highlight color, both given as RGB.
+    DUOTONE([u8; 3], [u8; 3]),
+    /// The given number of levels per color channel, from 2 to 255, for a flat poster look.
+    /// Fewer than 2 levels are raised to 2.
+    POSTERIZE(u8),
+    /// Black and white, split at the threshold.
+    ///
+    /// TIFF output is written with one bit per pixel and CCITT Group 4 compression,
+    /// whatever TIFF compression is set, PNG output as an image with a two color palette.
+    /// Other formats write the black and white pixels as grayscale.
+    BILEVEL(Threshold),
+}
+
+/// RGB color spaces the pixels can be converted to
+#[derive(Debug, Clone, Copy)]
+pub enum ColorSpace {
+    /// sRGB, the color space of the web and of most untagged images.
+    SRGB,
+    /// Adobe RGB (1998), a wider gamut common in print workflows.
+    #[allow(non_camel_case_types)]
+    ADOBE_RGB,
+    /// Display P3, the wide gamut of recent phone and computer displays.
+    #[allow(non_camel_case_types)]
+    DISPLAY_P3,
+}
+
+/// Tone mapping operators of `ColorProfile::TONE_MAP`
+#[derive(Debug, Clone, Copy)]
+pub enum ToneMapping {
+    /// Reinhard's operator, a soft roll-off that keeps the colors of the source.
+    REINHARD,
+    /// The filmic ACES curve, with more contrast and saturated highlights.
+    ACES,
+}
+
+/// Threshold separating the black from the white pixels of `ColorProfile::BILEVEL`.
+#[derive(Debug, Clone, Copy)]
+pub enum Threshold {
+    /// Pixels brighter than this value, from 0 to 255, become white.
+    VALUE(u8),
+    /// Pick the threshold of every image from its histogram with Otsu's method.
+    OTSU,
 }
@@ -0,0 +1,754 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+#[cfg(feature = "parallel")]
+use rayon::{prelude::*, ThreadPoolBuilder};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use self::manifest::{manifest, Record};
+use super::{
+    progress::ProgressFn, reader::is_image, ColorProfile, ImageType, Imfconv, Progress, Stage,
+    Written,
+};
+
+mod manifest;
+#[cfg(feature = "watch")]
+mod watch;
+
+/// File in the destination directory the hashes of the converted sources are recorded in.
+const HASHES_FILE: &str = ".imfconv-hashes";
+
+/// Settings applied to every source on top of those of `ImfconvBatch`.
+type Configure = dyn Fn(Imfconv) -> Result<Imfconv, Box<dyn Error>> + Send + Sync;
+
+/// What converting a source wrote or the message of its error, and the time it took.
+type Outcome = (Result<Written, String>, Duration);
+
+/// Outcome of a batch conversion
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    /// Sources that were converted, in the order of their paths.
+    pub converted: Vec<PathBuf>,
+    /// Sources that failed, in the order of their paths. Only batches that continue on
+    /// errors, see `ImfconvBatch::set_continue_on_error`, report failures here.
+    pub failed: Vec<BatchFailure>,
+    /// Conversions a dry run would make, see `ImfconvBatch::set_dry_run`, in the order of
+    /// their sources. Empty for batches that convert.
+    pub planned: Vec<PlannedConversion>,
+    /// Sources incremental batches left out as already converted, see
+    /// `ImfconvBatch::set_incremental`, in the order of their paths.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// How incremental batches tell that a source is already converted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Incremental {
+    /// The output exists and was modified after the source.
+    NEWER,
+    /// The output exists and the contents of the source have the SHA-256 hash recorded
+    /// when it was converted. The hashes are kept in the file `.imfconv-hashes` of the
+    /// destination directory, so sources copied or touched without a change are skipped.
+    HASH,
+}
+
+/// Conversion of a source planned by a dry run
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedConversion {
+    pub source: PathBuf,
+    /// The path the output would be written to, with the extension of the output format.
+    /// Conversions writing several files, e.g. split pages, name them after this path.
+    pub dest: PathBuf,
+    /// Whether the conversion would be skipped because `dest` exists,
+    /// see `Imfconv::set_overwrite_policy`.
+    pub skip: bool,
+}
+
+/// Source a batch failed to convert
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchFailure {
+    pub source: PathBuf,
+    /// Message of the error the conversion failed with.
+    pub error: String,
+}
+
+/// Counts of the files of a running batch, for the progress callback.
+struct Tally {
+    completed: AtomicUsize,
+    total: usize,
+}
+
+/// Conversion of every image in a directory with shared settings.
+///
+/// Each image is written to the destination directory under its own file name, with the
+/// extension replaced by that of the output format. Sources keep their format unless
+/// `set_image_format` is called. Files that are no images, judged by their contents,
+/// are left out, as are subdirectories unless `set_recursive` or `set_include` is called.
+pub struct ImfconvBatch {
+    source_dir: PathBuf,
+    dest_dir: PathBuf,
+    format: Option<ImageType>,
+    color_profile: Option<ColorProfile>,
+    quality: Option<u8>,
+    recursive: bool,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    #[cfg(feature = "parallel")]
+    threads: usize,
+    continue_on_error: bool,
+    dry_run: bool,
+    incremental: Option<Incremental>,
+    manifest: Option<PathBuf>,
+    configure: Option<Box<Configure>>,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl ImfconvBatch {
+    /// Returns an `Error` if `source_dir` is not a directory. `dest_dir` is created by
+    /// `convert` if it does not exist.
+    pub fn new(source_dir: &Path, dest_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        if !source_dir.is_dir() {
+            return Err(format!("{} is not a directory", source_dir.display()).into());
+        }
+        Ok(Self {
+            source_dir: source_dir.to_path_buf(),
+            dest_dir: dest_dir.to_path_buf(),
+            format: None,
+            color_profile: None,
+            quality: None,
+            recursive: false,
+            include: None,
+            exclude: None,
+            #[cfg(feature = "parallel")]
+            threads: 1,
+            continue_on_error: false,
+            dry_run: false,
+            incremental: None,
+            manifest: None,
+            configure: None,
+            progress: None,
+        })
+    }
+
+    pub fn set_image_format(mut self, image_type: &ImageType) -> Self {
+        self.format = Some(*image_type);
+        self
+    }
+
+    pub fn set_color_profile(mut self, color_profile: &ColorProfile) -> Self {
+        self.color_profile = Some(*color_profile);
+        self
+    }
+
+    /// Quality of lossy JPEG, WebP and AVIF output, see `Imfconv::set_jpeg_quality`,
+    /// `Imfconv::set_webp_quality` and `Imfconv::set_avif_quality` for the ranges.
+    pub fn set_quality(mut self, quality: u8) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Also convert the images in the subdirectories of the source directory.
+    ///
+    /// The directory tree is mirrored under the destination directory, subdirectories
+    /// are created as needed.
+    pub fn set_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Convert only the sources whose path relative to the source directory matches one of
+    /// the glob `patterns`, e.g. `photos/**/*.{jpg,png}`.
+    ///
+    /// `*` does not match across directories, `**` matches any number of them. The whole
+    /// tree of the source directory is searched. Returns an `Error` if a pattern is invalid.
+    pub fn set_include(mut self, patterns: &[&str]) -> Result<Self, Box<dyn Error>> {
+        self.include = Some(glob_set(patterns)?);
+        Ok(self)
+    }
+
+    /// Leave out the sources whose path relative to the source directory matches one of
+    /// the glob `patterns`, even if they match those of `set_include`.
+    ///
+    /// Returns an `Error` if a pattern is invalid.
+    pub fn set_exclude(mut self, patterns: &[&str]) -> Result<Self, Box<dyn Error>> {
+        self.exclude = Some(glob_set(patterns)?);
+        Ok(self)
+    }
+
+    /// Number of images converted at the same time, each on a thread of its own, 0 uses
+    /// one thread per CPU core. Defaults to 1, converting the images one after another.
+    ///
+    /// Available with the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    pub fn set_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Go on with the other images if one fails, e.g. because it is corrupt, instead of
+    /// aborting the batch. The failures are listed in the report of `convert`.
+    pub fn set_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Only plan the batch. `convert` then finds the sources, computes their destinations
+    /// and checks the settings, decoding no more than the headers of the images and writing
+    /// nothing. The planned conversions are listed in its report.
+    ///
+    /// Errors only found in the image data, or when encoding, are not found by a dry run.
+    pub fn set_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Only convert the sources that changed since they were last converted, as `incremental`
+    /// tells, so running a batch again converts new images only. `None`, the default,
+    /// converts every source.
+    ///
+    /// The outputs are looked up in the file system, so custom output sinks are not
+    /// supported. Conversions writing several files, e.g. split pages, are never skipped.
+    pub fn set_incremental(mut self, incremental: Option<Incremental>) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Write a JSON manifest of the batch to `path` once it is converted, for automation
+    /// that processes the outputs.
+    ///
+    /// The manifest lists every source with its status, `converted`, `failed` or `skipped`,
+    /// the paths of the files written, the width and height of the converted image, the sizes
+    /// of the source and the outputs in bytes, the time the conversion took in milliseconds
+    /// and the error message of failures. Values that do not apply are `null`. Batches that
+    /// fail, and dry runs, write no manifest.
+    pub fn set_manifest(mut self, path: &Path) -> Self {
+        self.manifest = Some(path.to_path_buf());
+        self
+    }
+
+    /// Apply further settings to the conversion of every source, e.g. a resize.
+    ///
+    /// `configure` is called with each `Imfconv` after the settings of the batch are applied.
+    pub fn configure<F>(mut self, configure: F) -> Self
+    where
+        F: Fn(Imfconv) -> Result<Imfconv, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        self.configure = Some(Box::new(configure));
+        self
+    }
+
+    /// Call `callback` at the start of every stage of the conversion of every image, see
+    /// `Imfconv::set_progress_callback`, with the counts of the whole batch.
+    ///
+    /// With several threads the stages of different images are interleaved.
+    pub fn set_progress_callback<F: Fn(Progress) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Execute the batch, converting the images in the order of their paths.
+    ///
+    /// Returns the report of the converted images. But returns the `Error` of the first
+    /// image that fails unless the batch continues on errors, the images after it are not
+    /// converted. With several threads, see `set_threads`, the images being converted at
+    /// that time are still finished.
+    pub fn convert(&self) -> Result<BatchReport, Box<dyn Error>> {
+        self.run(self.sources()?)
+    }
+
+    /// Convert or, for dry runs, plan `sources`.
+    fn run(&self, sources: Vec<PathBuf>) -> Result<BatchReport, Box<dyn Error>> {
+        let hashes_path = self.dest_dir.join(HASHES_FILE);
+        let mut hashes = match self.incremental {
+            Some(Incremental::HASH) => read_hashes(&hashes_path),
+            _ => HashMap::new(),
+        };
+        let mut pending = Vec::with_capacity(sources.len());
+        let mut pending_hashes = Vec::with_capacity(sources.len());
+        let mut skipped = Vec::new();
+        for source in sources {
+            let hash = match self.incremental {
+                Some(Incremental::HASH) => hash_file(&source),
+                _ => None,
+            };
+            match self.is_converted(&source, hash.as_deref(), &hashes) {
+                true => skipped.push(source),
+                false => {
+                    pending.push(source);
+                    pending_hashes.push(hash);
+                }
+            }
+        }
+        if self.dry_run {
+            let mut report = self.plan(pending)?;
+            report.skipped = skipped;
+            return Ok(report);
+        }
+
+        if let Err(e) = fs::create_dir_all(&self.dest_dir) {
+            return Err(Box::new(e));
+        }
+        let tally = Arc::new(Tally {
+            completed: AtomicUsize::new(0),
+            total: pending.len(),
+        });
+        let outcomes = self.convert_sources(&pending, &tally)?;
+        let mut records: Vec<Record> = skipped.iter().map(|s| Record::skipped(s)).collect();
+        for (source, (outcome, duration)) in pending.iter().zip(&outcomes) {
+            records.push(Record::converted(source, outcome, *duration));
+        }
+        records.sort_by(|a, b| a.source.cmp(b.source));
+        if let Some(path) = &self.manifest {
+            if let Err(e) = fs::write(path, manifest(&records)) {
+                return Err(Box::new(e));
+            }
+        }
+
+        let mut report = BatchReport {
+            skipped,
+            ..BatchReport::default()
+        };
+        let converted = pending.into_iter().zip(pending_hashes).zip(outcomes);
+        for ((source, hash), (outcome, _)) in converted {
+            match outcome {
+                Ok(_) => {
+                    if let Some(hash) = hash {
+                        hashes.insert(self.relative(&source), hash);
+                    }
+                    report.converted.push(source);
+                }
+                Err(error) => report.failed.push(BatchFailure { source, error }),
+            }
+        }
+        if self.incremental == Some(Incremental::HASH) {
+            write_hashes(&hashes_path, &hashes)?;
+        }
+        Ok(report)
+    }
+
+    /// Whether `source`, whose contents have `hash`, was converted before as the incremental
+    /// mode tells, with the `recorded` hashes of the converted sources.
+    fn is_converted(
+        &self,
+        source: &Path,
+        hash: Option<&str>,
+        recorded: &HashMap<String, String>,
+    ) -> bool {
+        let incremental = match self.incremental {
+            Some(i) => i,
+            None => return false,
+        };
+        // Sources that cannot be read are converted, so that the conversion reports them.
+        let output = match self.dest(source).and_then(|d| self.imfconv(source, &d)) {
+            Ok(i) => i.output_file(),
+            Err(_) => return false,
+        };
+        let output = match fs::metadata(output) {
+            Ok(m) => m,
+            Err(_) => return false,
+        };
+        match incremental {
+            Incremental::NEWER => {
+                let source = fs::metadata(source).and_then(|m| m.modified());
+                match (source, output.modified()) {
+                    (Ok(s), Ok(o)) => o > s,
+                    _ => false,
+                }
+            }
+            Incremental::HASH => {
+                hash.is_some() && recorded.get(&self.relative(source)).map(String::as_str) == hash
+            }
+        }
+    }
+
+    /// The report of a dry run for `sources`.
+    fn plan(&self, sources: Vec<PathBuf>) -> Result<BatchReport, Box<dyn Error>> {
+        let mut report = BatchReport::default();
+        for source in sources {
+            let planned = self
+                .dest(&source)
+                .and_then(|dest| self.imfconv(&source, &dest))
+                .and_then(|imfconv| imfconv.planned_path());
+            match planned {
+                Ok((dest, skip)) => {
+                    let conversion = PlannedConversion { source, dest, skip };
+                    report.planned.push(conversion)
+                }
+                Err(e) if !self.continue_on_error => return Err(e),
+                Err(e) => report.failed.push(BatchFailure {
+                    source,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Convert `sources`, returning the outcome of each, or the first error unless the
+    /// batch continues on errors.
+    fn convert_sources(
+        &self,
+        sources: &[PathBuf],
+        tally: &Arc<Tally>,
+    ) -> Result<Vec<Outcome>, Box<dyn Error>> {
+        #[cfg(feature = "parallel")]
+        if self.threads != 1 {
+            return self.convert_parallel(sources, tally);
+        }
+        let mut outcomes = Vec::with_capacity(sources.len());
+        for source in sources {
+            let start = Instant::now();
+            match self.convert_source(source, tally) {
+                Err(e) if !self.continue_on_error => return Err(e),
+                outcome => outcomes.push((outcome.map_err(|e| e.to_string()), start.elapsed())),
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// `convert_sources` on a pool of `threads` threads.
+    #[cfg(feature = "parallel")]
+    fn convert_parallel(
+        &self,
+        sources: &[PathBuf],
+        tally: &Arc<Tally>,
+    ) -> Result<Vec<Outcome>, Box<dyn Error>> {
+        let pool = match ThreadPoolBuilder::new().num_threads(self.threads).build() {
+            Ok(p) => p,
+            Err(e) => return Err(Box::new(e)),
+        };
+        // Errors are not `Send`, so only their messages are passed back from the threads.
+        let convert = |s: &PathBuf| {
+            let start = Instant::now();
+            let converted = self.convert_source(s, tally).map_err(|e| e.to_string());
+            (converted, start.elapsed())
+        };
+        if self.continue_on_error {
+            return Ok(pool.install(|| sources.par_iter().map(convert).collect()));
+        }
+        let outcomes = pool.install(|| {
+            sources
+                .par_iter()
+                .map(|s| match convert(s) {
+                    (Ok(written), duration) => Ok((Ok(written), duration)),
+                    (Err(e), _) => Err(e),
+                })
+                .collect()
+        });
+        match outcomes {
+            Ok(o) => Ok(o),
+            Err(e) => Err(String::into(e)),
+        }
+    }
+
+    /// Convert the image at `source`, reporting it to the progress callback as `FAILED`
+    /// if that fails.
+    fn convert_source(&self, source: &Path, tally: &Arc<Tally>) -> Result<Written, Box<dyn Error>> {
+        let converted = self.convert_to_dest(source, tally);
+        if let (Err(_), Some(progress)) = (&converted, &self.progress) {
+            progress(Progress {
+                source: source.to_path_buf(),
+                stage: Stage::FAILED,
+                completed: tally.completed.fetch_add(1, Ordering::SeqCst) + 1,
+                total: tally.total,
+            });
+        }
+        converted
+    }
+
+    /// Convert the image at `source` to its place under the destination directory.
+    fn convert_to_dest(
+        &self,
+        source: &Path,
+        tally: &Arc<Tally>,
+    ) -> Result<Written, Box<dyn Error>> {
+        let dest = self.dest(source)?;
+        if let Some(parent) = dest.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                return Err(Box::new(e));
+            }
+        }
+        let mut imfconv = self.imfconv(source, &dest)?;
+        if let Some(progress) = &self.progress {
+            let (progress, tally) = (progress.clone(), tally.clone());
+            imfconv = imfconv.set_progress_callback(move |p| {
+                let completed = match p.stage {
+                    Stage::DONE => tally.completed.fetch_add(1, Ordering::SeqCst) + 1,
+                    _ => tally.completed.load(Ordering::SeqCst),
+                };
+                progress(Progress {
+                    completed,
+                    total: tally.total,
+                    ..p
+                })
+            });
+        }
+        imfconv.write()
+    }
+
+    /// Path of `source` relative to the source directory, which its hash is recorded under.
+    fn relative(&self, source: &Path) -> String {
+        let relative = source.strip_prefix(&self.source_dir).unwrap_or(source);
+        relative.to_string_lossy().into_owned()
+    }
+
+    /// The destination of `source`, its place under the destination directory before the
+    /// extension is replaced.
+    fn dest(&self, source: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        match source.strip_prefix(&self.source_dir) {
+            Ok(p) => Ok(self.dest_dir.join(p)),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Image files inside the source directory, or its whole tree if recursive, selected by
+    /// the include and exclude patterns and sorted by path.
+    ///
+    /// A destination directory inside the source directory is left out, so the outputs of
+    /// earlier runs are not converted again.
+    fn sources(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let depth = match self.recursive || self.include.is_some() {
+            true => usize::MAX,
+            false => 1,
+        };
+        let walk = WalkDir::new(&self.source_dir)
+            .min_depth(1)
+            .max_depth(depth)
+            .into_iter()
+            .filter_entry(|e| {
+                self.dest_dir == self.source_dir || !e.path().starts_with(&self.dest_dir)
+            });
+        let mut sources = Vec::new();
+        for entry in walk {
+            let path = match entry {
+                Ok(e) => e.into_path(),
+                Err(e) => return Err(Box::new(e)),
+            };
+            if self.is_source(&path) {
+                sources.push(path);
+            }
+        }
+        sources.sort();
+        Ok(sources)
+    }
+
+    /// Whether the file at `path` is an image the batch converts.
+    fn is_source(&self, path: &Path) -> bool {
+        path.is_file() && self.selects(path) && is_image(path)
+    }
+
+    /// Whether the include and exclude patterns select the file at `path`.
+    fn selects(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.source_dir).unwrap_or(path);
+        let included = match &self.include {
+            Some(i) => i.is_match(relative),
+            None => true,
+        };
+        let excluded = match &self.exclude {
+            Some(e) => e.is_match(relative),
+            None => false,
+        };
+        included && !excluded
+    }
+
+    /// The conversion of `source` to `dest` with the settings of the batch.
+    fn imfconv(&self, source: &Path, dest: &Path) -> Result<Imfconv, Box<dyn Error>> {
+        let mut imfconv = Imfconv::new(source, dest)?;
+        if let Some(format) = &self.format {
+            imfconv = imfconv.set_image_format(format);
+        }
+        if let Some(color_profile) = &self.color_profile {
+            imfconv = imfconv.set_color_profile(color_profile)?;
+        }
+        if let Some(quality) = self.quality {
+            imfconv = imfconv
+                .set_jpeg_quality(quality)
+                .set_webp_quality(quality)
+                .set_avif_quality(quality);
+        }
+        match &self.configure {
+            Some(configure) => configure(imfconv),
+            None => Ok(imfconv),
+        }
+    }
+}
+
+/// Matcher of any of the glob `patterns`, where `*` does not match the path separator.
+fn glob_set(patterns: &[&str]) -> Result<GlobSet, Box<dyn Error>> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match GlobBuilder::new(pattern).literal_separator(true).build() {
+            Ok(g) => builder.add(g),
+            Err(e) => return Err(Box::new(e)),
+        };
+    }
+    match builder.build() {
+        Ok(s) => Ok(s),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Hex encoded SHA-256 hash of the contents of the file at `path`, `None` if it cannot
+/// be read.
+fn hash_file(path: &Path) -> Option<String> {
+    let digest = Sha256::digest(fs::read(path).ok()?);
+    Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Hashes recorded in the file at `path` by relative source path, in the format of
+/// `sha256sum`. A missing or unreadable file records no hashes.
+fn read_hashes(path: &Path) -> HashMap<String, String> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|l| l.split_once("  "))
+        .map(|(hash, source)| (source.to_string(), hash.to_string()))
+        .collect()
+}
+
+/// Record `hashes` in the file at `path`, sorted by relative source path.
+fn write_hashes(path: &Path, hashes: &HashMap<String, String>) -> Result<(), Box<dyn Error>> {
+    let mut sources: Vec<_> = hashes.iter().collect();
+    sources.sort();
+    let contents: String = sources
+        .into_iter()
+        .map(|(source, hash)| format!("{}  {}\n", hash, source))
+        .collect();
+    match fs::write(path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use image::RgbImage;
+
+    use super::{read_hashes, write_hashes, ImfconvBatch, Incremental, HASHES_FILE};
+    use crate::imfconv::ImageType;
+
+    /// An empty directory for the test `name` below the temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("imfconv-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write a small PNG for each of `names` into `dir` and return their paths.
+    fn write_images(dir: &Path, names: &[&str]) -> Vec<PathBuf> {
+        let image = RgbImage::from_pixel(8, 6, image::Rgb([200, 100, 50]));
+        let paths: Vec<PathBuf> = names.iter().map(|n| dir.join(n)).collect();
+        for path in &paths {
+            image.save(path).unwrap();
+        }
+        paths
+    }
+
+    /// Write a file that starts like a PNG but cannot be decoded.
+    fn write_broken(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        path
+    }
+
+    #[test]
+    fn report_keeps_the_source_order() {
+        let dir = temp_dir("order");
+        let source_dir = dir.join("src");
+        fs::create_dir(&source_dir).unwrap();
+        let names: Vec<String> = (0..12).map(|i| format!("{:02}.png", i)).collect();
+        let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+        let sources = write_images(&source_dir, &names);
+
+        let batch = ImfconvBatch::new(&source_dir, &dir.join("out"))
+            .unwrap()
+            .set_image_format(&ImageType::BMP);
+        #[cfg(feature = "parallel")]
+        let batch = batch.set_threads(0);
+        assert_eq!(batch.convert().unwrap().converted, sources);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn continue_on_error_reports_failures() {
+        let dir = temp_dir("continue");
+        let source_dir = dir.join("src");
+        fs::create_dir(&source_dir).unwrap();
+        let sources = write_images(&source_dir, &["a.png", "c.png"]);
+        let broken = write_broken(&source_dir, "b.png");
+
+        let batch = ImfconvBatch::new(&source_dir, &dir.join("out"))
+            .unwrap()
+            .set_image_format(&ImageType::BMP);
+        assert!(batch.convert().is_err());
+
+        let report = batch.set_continue_on_error(true).convert().unwrap();
+        assert_eq!(report.converted, sources);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].source, broken);
+        assert!(dir.join("out").join("c.bmp").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_round_trip() {
+        let dir = temp_dir("hashes");
+        let path = dir.join(HASHES_FILE);
+        let hashes: HashMap<String, String> = [
+            ("a.png", "01ab"),
+            ("sub dir/two  spaces.png", "cd23"),
+            ("sub/b.png", "ef45"),
+        ]
+        .into_iter()
+        .map(|(s, h)| (s.to_string(), h.to_string()))
+        .collect();
+        write_hashes(&path, &hashes).unwrap();
+        assert_eq!(read_hashes(&path), hashes);
+        assert!(read_hashes(&dir.join("missing")).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn incremental_hash_skips_unchanged_sources() {
+        let dir = temp_dir("incremental");
+        let source_dir = dir.join("src");
+        fs::create_dir(&source_dir).unwrap();
+        let sources = write_images(&source_dir, &["a.png", "b.png"]);
+
+        let batch = ImfconvBatch::new(&source_dir, &dir.join("out"))
+            .unwrap()
+            .set_image_format(&ImageType::BMP)
+            .set_incremental(Some(Incremental::HASH));
+        assert_eq!(batch.convert().unwrap().converted, sources);
+
+        let report = batch.convert().unwrap();
+        assert!(report.converted.is_empty());
+        assert_eq!(report.skipped, sources);
+
+        RgbImage::new(3, 3).save(&sources[1]).unwrap();
+        let report = batch.convert().unwrap();
+        assert_eq!(report.converted, vec![sources[1].clone()]);
+        assert_eq!(report.skipped, vec![sources[0].clone()]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fs, path::Path, time::Duration};
+
+use super::Written;
+
+/// What a batch did with one source, a line of the manifest.
+pub struct Record<'a> {
+    pub source: &'a Path,
+    written: Option<&'a Written>,
+    error: Option<&'a str>,
+    duration: Option<Duration>,
+}
+
+impl<'a> Record<'a> {
+    pub fn converted(
+        source: &'a Path,
+        outcome: &'a Result<Written, String>,
+        duration: Duration,
+    ) -> Self {
+        Self {
+            source,
+            written: outcome.as_ref().ok(),
+            error: outcome.as_ref().err().map(|e| e.as_str()),
+            duration: Some(duration),
+        }
+    }
+
+    pub fn skipped(source: &'a Path) -> Self {
+        Self {
+            source,
+            written: None,
+            error: None,
+            duration: None,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match (self.written, self.error) {
+            (Some(_), _) => "converted",
+            (None, Some(_)) => "failed",
+            (None, None) => "skipped",
+        }
+    }
+}
+
+/// The JSON manifest of the sources of a batch.
+pub fn manifest(records: &[Record]) -> String {
+    let files: Vec<String> = records.iter().map(entry).collect();
+    if files.is_empty() {
+        return String::from("{\n  \"files\": []\n}\n");
+    }
+    format!("{{\n  \"files\": [\n{}\n  ]\n}}\n", files.join(",\n"))
+}
+
+fn entry(record: &Record) -> String {
+    let bytes_in = fs::metadata(record.source).ok().map(|m| m.len());
+    let (destinations, width, height, bytes_out) = match record.written {
+        Some(w) => {
+            let destinations: Vec<String> = w
+                .files
+                .iter()
+                .map(|(path, _)| string(&path.to_string_lossy()))
+                .collect();
+            let bytes_out: usize = w.files.iter().map(|(_, len)| len).sum();
+            (
+                format!("[{}]", destinations.join(", ")),
+                w.width.to_string(),
+                w.height.to_string(),
+                bytes_out.to_string(),
+            )
+        }
+        None => (
+            String::from("[]"),
+            String::from("null"),
+            String::from("null"),
+            String::from("null"),
+        ),
+    };
+    format!(
+        "    {{\"source\": {}, \"status\": \"{}\", \"destinations\": {}, \"width\": {}, \
+         \"height\": {}, \"bytes_in\": {}, \"bytes_out\": {}, \"duration_ms\": {}, \
+         \"error\": {}}}",
+        string(&record.source.to_string_lossy()),
+        record.status(),
+        destinations,
+        width,
+        height,
+        or_null(bytes_in),
+        bytes_out,
+        or_null(record.duration.map(|d| d.as_millis())),
+        record.error.map_or(String::from("null"), string),
+    )
+}
+
+fn or_null<T: ToString>(value: Option<T>) -> String {
+    value.map_or(String::from("null"), |v| v.to_string())
+}
+
+/// `value` as a JSON string literal.
+fn string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use super::{manifest, string, Record};
+
+    #[test]
+    fn string_escapes_json() {
+        assert_eq!(string("plain.png"), "\"plain.png\"");
+        assert_eq!(string("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(string("C:\\photos"), "\"C:\\\\photos\"");
+        assert_eq!(string("a\nb\tc\u{1}"), "\"a\\u000ab\\u0009c\\u0001\"");
+        assert_eq!(string("café"), "\"café\"");
+    }
+
+    #[test]
+    fn manifest_escapes_sources_and_errors() {
+        let failed = Err(String::from("bad \"header\"\n"));
+        let records = [
+            Record::converted(
+                Path::new("dir/\"quoted\".png"),
+                &failed,
+                Duration::from_millis(7),
+            ),
+            Record::skipped(Path::new("dir\\b.png")),
+        ];
+        let json = manifest(&records);
+        assert!(json.contains(
+            "{\"source\": \"dir/\\\"quoted\\\".png\", \"status\": \"failed\", \
+             \"destinations\": [], \"width\": null, \"height\": null, \"bytes_in\": null, \
+             \"bytes_out\": null, \"duration_ms\": 7, \"error\": \"bad \\\"header\\\"\\u000a\"}"
+        ));
+        assert!(json.contains("\"source\": \"dir\\\\b.png\", \"status\": \"skipped\""));
+        assert_eq!(manifest(&[]), "{\n  \"files\": []\n}\n");
+    }
+}
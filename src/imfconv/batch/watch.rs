@@ -0,0 +1,91 @@
+use std::{
+    error::Error,
+    fs, mem,
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use notify::{event::ModifyKind, Event, EventKind, RecursiveMode, Watcher};
+
+use super::{BatchReport, ImfconvBatch};
+
+/// Time without any change after which the new and changed files are converted, so files
+/// still being written, e.g. by a scanner, are not read half way.
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+impl ImfconvBatch {
+    /// Watch the source directory and convert the images created in it, moved into it or
+    /// changed, with the settings of the batch. Available with the `watch` feature.
+    ///
+    /// Images already in the directory are left as they are, call `convert` first to convert
+    /// them too. The files are converted once nothing changed for a moment, and `on_batch`
+    /// is called with the report of each of these conversions.
+    ///
+    /// Blocks until watching fails, or an image fails unless the batch continues on errors,
+    /// and returns that `Error`. The destination directory must not be the source directory,
+    /// else the outputs would be converted again.
+    pub fn watch<F: FnMut(BatchReport)>(&self, mut on_batch: F) -> Result<(), Box<dyn Error>> {
+        if self.dest_dir == self.source_dir {
+            return Err("a watched directory cannot be the destination directory".into());
+        }
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(sender) {
+            Ok(w) => w,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let mode = match self.recursive || self.include.is_some() {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        };
+        if let Err(e) = watcher.watch(&self.source_dir, mode) {
+            return Err(Box::new(e));
+        }
+
+        let mut changed = Vec::new();
+        loop {
+            let received = match changed.is_empty() {
+                true => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                false => receiver.recv_timeout(QUIET_PERIOD),
+            };
+            match received {
+                Ok(Ok(event)) => changed.extend(changed_files(event)),
+                Ok(Err(e)) => return Err(Box::new(e)),
+                Err(RecvTimeoutError::Timeout) => {
+                    let sources = self.arrived(mem::take(&mut changed));
+                    if !sources.is_empty() {
+                        on_batch(self.run(sources)?);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+
+    /// The images of the batch among the `changed` files, sorted by path.
+    fn arrived(&self, changed: Vec<PathBuf>) -> Vec<PathBuf> {
+        // Some platforms report canonical paths, which are mapped back below the source
+        // directory as it was given.
+        let root = fs::canonicalize(&self.source_dir).unwrap_or_else(|_| self.source_dir.clone());
+        let mut sources: Vec<PathBuf> = changed
+            .into_iter()
+            .map(|p| match p.strip_prefix(&root) {
+                Ok(relative) => self.source_dir.join(relative),
+                Err(_) => p,
+            })
+            .filter(|p| !p.starts_with(&self.dest_dir) && self.is_source(p))
+            .collect();
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+}
+
+/// Files `event` reports as created or changed.
+fn changed_files(event: Event) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => Vec::new(),
+        EventKind::Create(_) | EventKind::Modify(_) => event.paths,
+        _ => Vec::new(),
+    }
+}
@@ -1,2 +1,70 @@
+use image::{DynamicImage, Rgb32FImage};
+
+pub mod animation;
 pub mod color_profile;
+pub mod color_space;
+pub mod composite;
 pub mod format;
+pub mod metadata;
+pub mod montage;
+pub mod palette;
+pub mod sprite_sheet;
+pub mod srgb;
+pub mod text;
+pub mod transform;
+
+/// Whether the image holds 16 bit integer samples, e.g. from a 16 bit PNG or TIFF.
+pub fn is_16bit(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_)
+    )
+}
+
+/// Whether the image holds floating point samples, e.g. HDR data read from OpenEXR.
+///
+/// Float samples are kept in linear light, while integer samples are sRGB encoded.
+pub fn is_float(image: &DynamicImage) -> bool {
+    matches!(
+        image,
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+    )
+}
+
+/// `rgb` with the sample type of `source`, float images stay float and integer images
+/// keep their 8 or 16 bits.
+pub fn with_precision_of(source: &DynamicImage, rgb: Rgb32FImage) -> DynamicImage {
+    let d = DynamicImage::ImageRgb32F(rgb);
+    if is_float(source) {
+        return d;
+    }
+    if is_16bit(source) {
+        return DynamicImage::ImageRgb16(d.to_rgb16());
+    }
+    DynamicImage::ImageRgb8(d.to_rgb8())
+}
+
+/// Composite `image` over the opaque `background` color, which removes its alpha channel.
+///
+/// Float images are blended in linear light, integer images in their sRGB encoding.
+pub fn flatten(image: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let float = is_float(image);
+    let background = background.map(|c| match float {
+        true => srgb::decode(c as f32 / 255.0),
+        false => c as f32 / 255.0,
+    });
+    let rgba = image.to_rgba32f();
+    let mut rgb = Rgb32FImage::new(rgba.width(), rgba.height());
+    for (pixel, source) in rgb.pixels_mut().zip(rgba.pixels()) {
+        let [r, g, b, a] = source.0;
+        let a = a.clamp(0.0, 1.0);
+        let color = [r, g, b];
+        for c in 0..3 {
+            pixel.0[c] = color[c] * a + background[c] * (1.0 - a);
+        }
+    }
+    with_precision_of(image, rgb)
+}
@@ -0,0 +1,15 @@
+use image::DynamicImage;
+
+/// Frames of an animated image, all of the same size.
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    /// Number of times the animation is played, 0 repeats it forever.
+    pub loop_count: u16,
+}
+
+/// One frame of an animation.
+pub struct AnimationFrame {
+    pub image: DynamicImage,
+    /// Time the frame is shown, in milliseconds.
+    pub delay_ms: u32,
+}
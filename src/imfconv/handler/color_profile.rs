@@ -1,8 +1,34 @@
 use std::error::Error;
 
+use image::DynamicImage;
+
+pub mod bilevel;
+pub mod cmyk;
+pub mod duotone;
 pub mod grayscale;
+pub mod grayscale_alpha;
+pub mod invert;
+pub mod posterize;
 pub mod rgb;
+pub mod rgba;
+pub mod sepia;
+pub mod tone_map;
 
+/// Pixel transform applied to every image and animation frame before it is encoded.
+///
+/// `edit` receives the decoded image, already rotated by its EXIF orientation.
+/// 8 and 16 bit samples are sRGB encoded, float samples are in linear light.
+/// The returned image is handed to the output format as it is, return the variant of
+/// `DynamicImage` with the channels and precision the output should be written with.
 pub trait ImfconvColorProfile {
-    fn edit(&self, width: u32, height: u32, raw_image: &[u8]) -> Result<Vec<u8>, Box<dyn Error>>;
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>>;
+
+    /// Whether `edit` keeps the alpha channel of the images that have one.
+    ///
+    /// Translucent images are composited over the background color before they are passed
+    /// to profiles that drop alpha, so the colors hidden under transparent pixels do not
+    /// show up in the output.
+    fn keeps_alpha(&self) -> bool {
+        false
+    }
 }
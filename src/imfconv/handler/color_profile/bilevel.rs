@@ -0,0 +1,61 @@
+use std::error::Error;
+
+use image::{DynamicImage, GrayImage};
+
+use super::ImfconvColorProfile;
+use crate::imfconv::{handler::format::to_rgb8, Threshold};
+
+/// Converts to black and white pixels, for fax and OCR pipelines.
+///
+/// The result is an 8 bit grayscale image holding only 0 and 255,
+/// which TIFF and PNG output store with one bit per pixel.
+pub struct Bilevel {
+    pub threshold: Threshold,
+}
+
+impl ImfconvColorProfile for Bilevel {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let mut gray = DynamicImage::ImageRgb8(to_rgb8(image)).to_luma8();
+        let threshold = match self.threshold {
+            Threshold::VALUE(t) => t,
+            Threshold::OTSU => otsu(&gray),
+        };
+        for pixel in gray.pixels_mut() {
+            pixel.0[0] = if pixel.0[0] > threshold { 255 } else { 0 };
+        }
+        Ok(DynamicImage::ImageLuma8(gray))
+    }
+}
+
+/// The threshold that maximizes the variance between the dark and the bright pixels.
+fn otsu(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    let sum: u64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(v, n)| v as u64 * n)
+        .sum();
+
+    let (mut best, mut best_variance) = (0, 0.0);
+    let (mut dark, mut dark_sum) = (0u64, 0u64);
+    for (value, count) in histogram.iter().enumerate() {
+        dark += count;
+        dark_sum += value as u64 * count;
+        let bright = total - dark;
+        if dark == 0 || bright == 0 {
+            continue;
+        }
+        let dark_mean = dark_sum as f64 / dark as f64;
+        let bright_mean = (sum - dark_sum) as f64 / bright as f64;
+        let variance = dark as f64 * bright as f64 * (dark_mean - bright_mean).powi(2);
+        if variance > best_variance {
+            best = value as u8;
+            best_variance = variance;
+        }
+    }
+    best
+}
@@ -0,0 +1,31 @@
+use std::error::Error;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::format::to_rgb8;
+
+/// Converts to the CMYK inks of print output.
+///
+/// `DynamicImage` has no CMYK variant, so the cyan, magenta, yellow and black inks are
+/// stored in the four channels of an 8 bit RGBA image, 255 being full ink coverage.
+/// The conversion is not color managed: black replaces the gray share of the colors
+/// and the remaining inks are the complements of the color channels.
+pub struct CmykColor;
+
+impl ImfconvColorProfile for CmykColor {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let rgb = to_rgb8(image);
+        let mut cmyk = RgbaImage::new(rgb.width(), rgb.height());
+        for (ink, pixel) in cmyk.pixels_mut().zip(rgb.pixels()) {
+            let [r, g, b] = pixel.0;
+            let max = r.max(g).max(b) as u32;
+            let color_ink = |c: u8| match max {
+                0 => 0,
+                _ => (((max - c as u32) * 255 + max / 2) / max) as u8,
+            };
+            *ink = Rgba([color_ink(r), color_ink(g), color_ink(b), 255 - max as u8]);
+        }
+        Ok(DynamicImage::ImageRgba8(cmyk))
+    }
+}
@@ -0,0 +1,36 @@
+use std::error::Error;
+
+use image::{DynamicImage, Rgb32FImage};
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{format::to_srgb, is_float, srgb, with_precision_of};
+
+/// Maps the luminance onto the gradient from the `shadow` to the `highlight` color.
+pub struct Duotone {
+    pub shadow: [u8; 3],
+    pub highlight: [u8; 3],
+}
+
+impl ImfconvColorProfile for Duotone {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let float = is_float(image);
+        // The gradient runs through sRGB encoded values, also for linear float samples.
+        let luma = to_srgb(image).to_luma32f();
+        let mut rgb = Rgb32FImage::new(luma.width(), luma.height());
+        for (pixel, l) in rgb.pixels_mut().zip(luma.iter()) {
+            let t = l.clamp(0.0, 1.0);
+            for c in 0..3 {
+                let (from, to) = (
+                    self.shadow[c] as f32 / 255.0,
+                    self.highlight[c] as f32 / 255.0,
+                );
+                let value = from + (to - from) * t;
+                pixel.0[c] = match float {
+                    true => srgb::decode(value),
+                    false => value,
+                };
+            }
+        }
+        Ok(with_precision_of(image, rgb))
+    }
+}
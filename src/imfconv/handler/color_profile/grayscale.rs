@@ -1,16 +1,21 @@
-use std::{error::Error, fmt::Error as FmtError};
+use std::error::Error;
+
+use image::DynamicImage;
 
 use super::ImfconvColorProfile;
+use crate::imfconv::handler::{is_16bit, is_float};
 
 pub struct Grayscale;
 
 impl ImfconvColorProfile for Grayscale {
-    fn edit(&self, width: u32, height: u32, raw_image: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let d = match image::RgbImage::from_vec(width, height, raw_image.to_vec()) {
-            Some(i) => image::DynamicImage::from(i),
-            None => return Err(Box::new(FmtError)),
-        };
-        let d = d.grayscale().into_rgb8();
-        return Ok(d.to_vec());
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let d = image.grayscale();
+        if is_float(image) {
+            return Ok(DynamicImage::ImageRgb32F(d.to_rgb32f()));
+        }
+        if is_16bit(image) {
+            return Ok(DynamicImage::ImageLuma16(d.to_luma16()));
+        }
+        Ok(DynamicImage::ImageLuma8(d.to_luma8()))
     }
 }
@@ -0,0 +1,26 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{is_16bit, is_float};
+
+/// Converts to grayscale like `Grayscale`, but keeps the alpha channel.
+pub struct GrayscaleAlpha;
+
+impl ImfconvColorProfile for GrayscaleAlpha {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let d = image.grayscale();
+        if is_float(image) {
+            return Ok(DynamicImage::ImageRgba32F(d.to_rgba32f()));
+        }
+        if is_16bit(image) {
+            return Ok(DynamicImage::ImageLumaA16(d.to_luma_alpha16()));
+        }
+        Ok(DynamicImage::ImageLumaA8(d.to_luma_alpha8()))
+    }
+
+    fn keeps_alpha(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,30 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{format::to_srgb, is_16bit, is_float, srgb};
+
+/// Inverts the colors, turning scanned film negatives into positives.
+///
+/// Float samples are inverted in sRGB encoding, clamped to 0.0..1.0,
+/// so the result looks like the inverted integer image.
+pub struct Invert;
+
+impl ImfconvColorProfile for Invert {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        if is_float(image) {
+            let mut rgb = to_srgb(image).to_rgb32f();
+            for sample in rgb.iter_mut() {
+                *sample = srgb::decode(1.0 - sample.clamp(0.0, 1.0));
+            }
+            return Ok(DynamicImage::ImageRgb32F(rgb));
+        }
+        let mut d = match is_16bit(image) {
+            true => DynamicImage::ImageRgb16(image.to_rgb16()),
+            false => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        d.invert();
+        Ok(d)
+    }
+}
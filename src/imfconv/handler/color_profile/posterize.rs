@@ -0,0 +1,28 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{format::to_srgb, is_float, srgb, with_precision_of};
+
+/// Reduces every color channel to `levels` evenly spaced levels.
+pub struct Posterize {
+    pub levels: u8,
+}
+
+impl ImfconvColorProfile for Posterize {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let float = is_float(image);
+        let steps = (self.levels.max(2) - 1) as f32;
+        // The levels are spaced evenly in sRGB encoding, also for linear float samples.
+        let mut rgb = to_srgb(image).to_rgb32f();
+        for sample in rgb.iter_mut() {
+            let value = (sample.clamp(0.0, 1.0) * steps).round() / steps;
+            *sample = match float {
+                true => srgb::decode(value),
+                false => value,
+            };
+        }
+        Ok(with_precision_of(image, rgb))
+    }
+}
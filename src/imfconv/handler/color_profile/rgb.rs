@@ -1,19 +1,21 @@
-use std::{error::Error, fmt::Error as FmtError};
+use std::error::Error;
+
+use image::DynamicImage;
 
 use super::ImfconvColorProfile;
+use crate::imfconv::handler::{is_16bit, is_float};
 
 pub struct RgbColor;
 
 impl ImfconvColorProfile for RgbColor {
-    fn edit(&self, width: u32, height: u32, raw_image: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let raw_image = match image::RgbImage::from_vec(width, height, raw_image.to_vec()) {
-            Some(i) => image::DynamicImage::from(i),
-            None => return Err(Box::new(FmtError)),
-        };
-        let rgb = match raw_image.as_rgb8() {
-            Some(i) => i,
-            None => return Err(Box::new(FmtError)),
-        };
-        return Ok(rgb.to_vec());
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        // Keep float samples as they are, so HDR data survives until encoding.
+        if is_float(image) {
+            return Ok(DynamicImage::ImageRgb32F(image.to_rgb32f()));
+        }
+        if is_16bit(image) {
+            return Ok(DynamicImage::ImageRgb16(image.to_rgb16()));
+        }
+        Ok(DynamicImage::ImageRgb8(image.to_rgb8()))
     }
 }
@@ -0,0 +1,25 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{is_16bit, is_float};
+
+/// Converts to RGB like `RgbColor`, but keeps the alpha channel.
+pub struct RgbaColor;
+
+impl ImfconvColorProfile for RgbaColor {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        if is_float(image) {
+            return Ok(DynamicImage::ImageRgba32F(image.to_rgba32f()));
+        }
+        if is_16bit(image) {
+            return Ok(DynamicImage::ImageRgba16(image.to_rgba16()));
+        }
+        Ok(DynamicImage::ImageRgba8(image.to_rgba8()))
+    }
+
+    fn keeps_alpha(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,35 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::ImfconvColorProfile;
+use crate::imfconv::handler::{is_float, with_precision_of};
+
+/// Weights of the red, green and blue source channels in each output channel.
+const SEPIA: [[f32; 3]; 3] = [
+    [0.393, 0.769, 0.189],
+    [0.349, 0.686, 0.168],
+    [0.272, 0.534, 0.131],
+];
+
+/// Tones the image in the warm brown of aged photographs.
+pub struct Sepia;
+
+impl ImfconvColorProfile for Sepia {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        let float = is_float(image);
+        let mut rgb = image.to_rgb32f();
+        for pixel in rgb.pixels_mut() {
+            let [r, g, b] = pixel.0;
+            pixel.0 = SEPIA.map(|w| {
+                let value = w[0] * r + w[1] * g + w[2] * b;
+                // Float samples may exceed 1.0, integer samples saturate at white.
+                match float {
+                    true => value,
+                    false => value.min(1.0),
+                }
+            });
+        }
+        Ok(with_precision_of(image, rgb))
+    }
+}
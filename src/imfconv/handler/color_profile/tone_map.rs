@@ -0,0 +1,33 @@
+use std::error::Error;
+
+use image::DynamicImage;
+
+use super::{rgb::RgbColor, ImfconvColorProfile};
+use crate::imfconv::{handler::is_float, ToneMapping};
+
+/// Compresses the unbounded linear light of float HDR images into the displayable range.
+///
+/// Integer images are already in that range and are converted like `RgbColor`.
+pub struct ToneMap {
+    pub operator: ToneMapping,
+}
+
+impl ImfconvColorProfile for ToneMap {
+    fn edit(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        if !is_float(image) {
+            return RgbColor.edit(image);
+        }
+        let mut rgb = image.to_rgb32f();
+        for sample in rgb.iter_mut() {
+            let x = sample.max(0.0);
+            *sample = match self.operator {
+                ToneMapping::REINHARD => x / (1.0 + x),
+                // Krzysztof Narkowicz's fit of the ACES filmic curve.
+                ToneMapping::ACES => {
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+                }
+            };
+        }
+        Ok(DynamicImage::ImageRgb32F(rgb))
+    }
+}
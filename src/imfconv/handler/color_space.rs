@@ -0,0 +1,124 @@
+use std::error::Error;
+
+use image::{DynamicImage, ImageBuffer, Pixel, Rgb, Rgba};
+use moxcms::{
+    CmsError, ColorProfile as IccProfile, DataColorSpace, Layout, TransformExecutor,
+    TransformOptions,
+};
+
+use super::{format::to_srgb, is_16bit, is_float, srgb};
+use crate::imfconv::ColorSpace;
+
+type Buffer<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+/// The ICC profile describing `color_space`.
+pub fn icc_profile(color_space: &ColorSpace) -> IccProfile {
+    match color_space {
+        ColorSpace::SRGB => IccProfile::new_srgb(),
+        ColorSpace::ADOBE_RGB => IccProfile::new_adobe_rgb(),
+        ColorSpace::DISPLAY_P3 => IccProfile::new_display_p3(),
+    }
+}
+
+/// Convert the pixels of `image` from the color space of the ICC profile `source`
+/// to `target`, keeping the alpha channel and the precision of the samples.
+///
+/// Sources without a profile, or with a profile that does not describe RGB or gray
+/// samples, are taken as sRGB. Float samples are always linear sRGB and ignore `source`.
+/// The result is an RGB image, grayscale sources are expanded.
+pub fn convert(
+    image: &DynamicImage,
+    source: Option<&[u8]>,
+    target: &IccProfile,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let source = match source {
+        Some(s) if !is_float(image) => IccProfile::new_from_slice(s)?,
+        _ => IccProfile::new_srgb(),
+    };
+    let source = match source.color_space {
+        DataColorSpace::Rgb | DataColorSpace::Gray => source,
+        _ => IccProfile::new_srgb(),
+    };
+    let gray = source.color_space == DataColorSpace::Gray;
+    let alpha = image.color().has_alpha();
+    let (src_layout, dst_layout) = match (gray, alpha) {
+        (true, true) => (Layout::GrayAlpha, Layout::Rgba),
+        (true, false) => (Layout::Gray, Layout::Rgb),
+        (false, true) => (Layout::Rgba, Layout::Rgba),
+        (false, false) => (Layout::Rgb, Layout::Rgb),
+    };
+    let options = TransformOptions::default();
+    let (width, height) = (image.width(), image.height());
+    let pixels = width as usize * height as usize;
+
+    if is_float(image) {
+        let encoded = to_srgb(image);
+        let samples = match alpha {
+            true => encoded.to_rgba32f().into_raw(),
+            false => encoded.to_rgb32f().into_raw(),
+        };
+        let transform = source.create_transform_f32(src_layout, target, dst_layout, options)?;
+        let mut converted = run(&samples, pixels, dst_layout, transform.as_ref())?;
+        // Decode the color channels back to linear light, alpha stays as it is.
+        let channels = dst_layout.channels();
+        for (i, sample) in converted.iter_mut().enumerate() {
+            if i % channels < 3 {
+                *sample = srgb::decode(*sample);
+            }
+        }
+        return Ok(match alpha {
+            true => DynamicImage::ImageRgba32F(buffer::<Rgba<f32>>(width, height, converted)?),
+            false => DynamicImage::ImageRgb32F(buffer::<Rgb<f32>>(width, height, converted)?),
+        });
+    }
+    if is_16bit(image) {
+        let samples = match (gray, alpha) {
+            (true, true) => image.to_luma_alpha16().into_raw(),
+            (true, false) => image.to_luma16().into_raw(),
+            (false, true) => image.to_rgba16().into_raw(),
+            (false, false) => image.to_rgb16().into_raw(),
+        };
+        let transform = source.create_transform_16bit(src_layout, target, dst_layout, options)?;
+        let converted = run(&samples, pixels, dst_layout, transform.as_ref())?;
+        return Ok(match alpha {
+            true => DynamicImage::ImageRgba16(buffer::<Rgba<u16>>(width, height, converted)?),
+            false => DynamicImage::ImageRgb16(buffer::<Rgb<u16>>(width, height, converted)?),
+        });
+    }
+    let samples = match (gray, alpha) {
+        (true, true) => image.to_luma_alpha8().into_raw(),
+        (true, false) => image.to_luma8().into_raw(),
+        (false, true) => image.to_rgba8().into_raw(),
+        (false, false) => image.to_rgb8().into_raw(),
+    };
+    let transform = source.create_transform_8bit(src_layout, target, dst_layout, options)?;
+    let converted = run(&samples, pixels, dst_layout, transform.as_ref())?;
+    Ok(match alpha {
+        true => DynamicImage::ImageRgba8(buffer::<Rgba<u8>>(width, height, converted)?),
+        false => DynamicImage::ImageRgb8(buffer::<Rgb<u8>>(width, height, converted)?),
+    })
+}
+
+/// Transform the pixels in `samples` to `pixels` pixels of the layout `dst_layout`.
+fn run<V: Copy + Default>(
+    samples: &[V],
+    pixels: usize,
+    dst_layout: Layout,
+    transform: &(dyn TransformExecutor<V> + Send + Sync),
+) -> Result<Vec<V>, CmsError> {
+    let mut converted = vec![V::default(); pixels * dst_layout.channels()];
+    transform.transform(samples, &mut converted)?;
+    Ok(converted)
+}
+
+/// The image buffer of `width` x `height` pixels holding `samples`.
+fn buffer<P: Pixel>(
+    width: u32,
+    height: u32,
+    samples: Vec<P::Subpixel>,
+) -> Result<Buffer<P>, Box<dyn Error>> {
+    match ImageBuffer::from_raw(width, height, samples) {
+        Some(b) => Ok(b),
+        None => Err("the color space conversion returned too few samples".into()),
+    }
+}
@@ -0,0 +1,93 @@
+use image::{DynamicImage, ImageBuffer, Rgba};
+
+use super::{format::to_srgb, is_16bit};
+use crate::imfconv::BlendMode;
+
+/// Placement of a source image in a stack of layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer {
+    /// Offset of the left edge from the left edge of the canvas, may be negative.
+    pub x: i64,
+    /// Offset of the top edge from the top edge of the canvas, may be negative.
+    pub y: i64,
+    /// From 0.0, invisible, to 1.0, opaque.
+    pub opacity: f32,
+    pub blend: BlendMode,
+}
+
+/// Flatten `images` from the bottom to the top, every image placed by its entry in `layers`.
+///
+/// The canvas has the size of the bottom image and is transparent where no layer covers it,
+/// layers are clipped to it. The result holds 16 bit samples if a source does and an alpha
+/// channel unless the bottom layer covers the canvas opaquely.
+pub fn composite(images: &[DynamicImage], layers: &[Layer]) -> DynamicImage {
+    let (width, height) = match images.first() {
+        Some(i) => (i.width(), i.height()),
+        None => (1, 1),
+    };
+    let mut canvas = ImageBuffer::from_pixel(width, height, Rgba([0.0f32; 4]));
+    for (image, layer) in images.iter().zip(layers) {
+        let source = to_srgb(image).to_rgba32f();
+        let opacity = layer.opacity.clamp(0.0, 1.0);
+        for (x, y, pixel) in source.enumerate_pixels() {
+            let (cx, cy) = (layer.x + x as i64, layer.y + y as i64);
+            if cx < 0 || cy < 0 {
+                continue;
+            }
+            let backdrop = match canvas.get_pixel_mut_checked(cx as u32, cy as u32) {
+                Some(p) => p,
+                None => continue,
+            };
+            blend_pixel(backdrop, pixel, opacity, layer.blend);
+        }
+    }
+
+    let image = DynamicImage::ImageRgba32F(canvas);
+    let covered = match (images.first(), layers.first()) {
+        (Some(i), Some(l)) => l.x == 0 && l.y == 0 && l.opacity >= 1.0 && !i.color().has_alpha(),
+        _ => false,
+    };
+    match (images.iter().any(is_16bit), covered) {
+        (true, false) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        (true, true) => DynamicImage::ImageRgb16(image.to_rgb16()),
+        (false, false) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        (false, true) => DynamicImage::ImageRgb8(image.to_rgb8()),
+    }
+}
+
+/// Composite `source` at `opacity` over `backdrop` with the separable blend modes of
+/// the W3C compositing specification.
+fn blend_pixel(backdrop: &mut Rgba<f32>, source: &Rgba<f32>, opacity: f32, mode: BlendMode) {
+    let a = source.0[3] * opacity;
+    let backdrop_a = backdrop.0[3];
+    let out_a = a + backdrop_a * (1.0 - a);
+    if out_a <= 0.0 {
+        return;
+    }
+    for c in 0..3 {
+        let (cs, cb) = (source.0[c], backdrop.0[c]);
+        let mixed = (1.0 - backdrop_a) * cs + backdrop_a * blend(cb, cs, mode);
+        backdrop.0[c] = (a * mixed + backdrop_a * (1.0 - a) * cb) / out_a;
+    }
+    backdrop.0[3] = out_a;
+}
+
+/// The color of the source sample `cs` blended with the backdrop sample `cb`.
+fn blend(cb: f32, cs: f32, mode: BlendMode) -> f32 {
+    let multiply = |cb: f32, cs: f32| cb * cs;
+    let screen = |cb: f32, cs: f32| cb + cs - cb * cs;
+    match mode {
+        BlendMode::NORMAL => cs,
+        BlendMode::MULTIPLY => multiply(cb, cs),
+        BlendMode::SCREEN => screen(cb, cs),
+        BlendMode::OVERLAY => match cb <= 0.5 {
+            true => multiply(cs, 2.0 * cb),
+            false => screen(cs, 2.0 * cb - 1.0),
+        },
+        BlendMode::DARKEN => cb.min(cs),
+        BlendMode::LIGHTEN => cb.max(cs),
+        BlendMode::DIFFERENCE => (cb - cs).abs(),
+        BlendMode::ADD => (cb + cs).min(1.0),
+    }
+}
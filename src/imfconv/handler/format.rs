@@ -1,15 +1,351 @@
-use std::{error::Error, path::Path};
+use std::{error::Error, io::Cursor};
 
+use image::{
+    DynamicImage, ImageBuffer, ImageOutputFormat, Rgb, Rgb32FImage, RgbImage, Rgba, RgbaImage,
+};
+
+use super::{animation::Animation, is_float, metadata::Metadata, srgb};
+use crate::imfconv::{
+    BitDepth, ChromaSubsampling, DdsCompression, Dither, PdfCompression, PngCompression, PngFilter,
+    PnmEncoding, TiffCompression,
+};
+
+pub mod apng;
+pub mod avif;
+pub mod bmp;
+pub mod dds;
+pub mod exr;
+pub mod farbfeld;
+pub mod gif;
+pub mod ico;
 pub mod jpeg;
+#[cfg(feature = "jxl")]
+pub mod jxl;
+pub mod pdf;
 pub mod png;
+pub mod pnm;
+pub mod qoi;
+pub mod tga;
 pub mod tiff;
+pub mod webp;
+
+/// Encoder settings shared by every format handler.
+///
+/// Each handler reads only the fields that apply to its own format.
+pub struct EncodeOptions {
+    /// Metadata written to the output unless `strip_metadata` is set.
+    pub metadata: Metadata,
+    /// Write no metadata at all.
+    pub strip_metadata: bool,
+    /// Physical resolution declared in the output, in dots per inch.
+    pub dpi: Option<u32>,
+    /// The channels of the RGBA images to encode hold CMYK inks, see `CmykColor`.
+    pub cmyk: bool,
+    /// The images to encode are black and white, see `Bilevel`.
+    pub bilevel: bool,
+    /// Bits per sample of PNG and TIFF output.
+    pub bit_depth: BitDepth,
+    /// Dithering used when colors are reduced to a palette, for GIF and indexed PNG output.
+    pub dither: Dither,
+    /// JPEG quality from 1 to 100, also used for JPEG compressed PDF output.
+    pub jpeg_quality: u8,
+    /// Encode JPEG progressively instead of as a single baseline scan.
+    pub jpeg_progressive: bool,
+    /// Lower the JPEG or lossy WebP quality until the output fits into this many bytes.
+    pub target_size_bytes: Option<usize>,
+    /// Build Huffman tables for every JPEG image instead of using the standard tables.
+    pub jpeg_optimize: bool,
+    /// Resolution of the color channels of JPEG output relative to the brightness.
+    pub jpeg_subsampling: ChromaSubsampling,
+    /// zlib compression level of PNG and APNG output.
+    pub png_compression: PngCompression,
+    /// Row filter of PNG and APNG output.
+    pub png_filter: PngFilter,
+    /// Quantize PNG output to an indexed image with at most this many colors (2 to 256).
+    pub png_palette_colors: Option<usize>,
+    /// Interlace PNG output with Adam7.
+    pub png_interlace: bool,
+    /// Compression of every page of TIFF output.
+    pub tiff_compression: TiffCompression,
+    /// Encode WebP losslessly. `webp_quality` is ignored while this is set.
+    pub webp_lossless: bool,
+    /// Lossy WebP quality from 0 to 100.
+    pub webp_quality: u8,
+    /// AVIF quality from 1 to 100.
+    pub avif_quality: u8,
+    /// AVIF encoder speed from 1 (slowest, smallest output) to 10 (fastest).
+    pub avif_speed: u8,
+    /// Edge lengths of the square images embedded in ICO output, from 1 to 256.
+    pub ico_sizes: Vec<u32>,
+    /// Sample encoding of PNM output.
+    pub pnm_encoding: PnmEncoding,
+    /// Block compression of DDS output.
+    pub dds_compression: DdsCompression,
+    /// Compression of the image embedded in PDF output.
+    pub pdf_compression: PdfCompression,
+    /// Delay of every frame of animated output in milliseconds, overriding the source.
+    pub frame_delay_ms: Option<u32>,
+    /// Number of plays of animated output, 0 repeats forever, overriding the source.
+    pub loop_count: Option<u16>,
+    /// Encode JPEG XL losslessly. The lossy mode needs the `jxl-lossy` feature.
+    #[cfg(feature = "jxl")]
+    pub jxl_lossless: bool,
+    /// Lossy JPEG XL quality from 0 to 100.
+    #[cfg(feature = "jxl")]
+    pub jxl_quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            metadata: Metadata::default(),
+            strip_metadata: false,
+            dpi: None,
+            cmyk: false,
+            bilevel: false,
+            bit_depth: BitDepth::EIGHT,
+            dither: Dither::NONE,
+            jpeg_quality: 75,
+            jpeg_progressive: false,
+            target_size_bytes: None,
+            jpeg_optimize: false,
+            jpeg_subsampling: ChromaSubsampling::S444,
+            png_compression: PngCompression::FAST,
+            png_filter: PngFilter::ADAPTIVE,
+            png_palette_colors: None,
+            png_interlace: false,
+            tiff_compression: TiffCompression::NONE,
+            webp_lossless: false,
+            webp_quality: 80,
+            avif_quality: 80,
+            avif_speed: 4,
+            ico_sizes: vec![16, 32, 48, 256],
+            pnm_encoding: PnmEncoding::BINARY,
+            dds_compression: DdsCompression::NONE,
+            pdf_compression: PdfCompression::FLATE,
+            frame_delay_ms: None,
+            loop_count: None,
+            #[cfg(feature = "jxl")]
+            jxl_lossless: true,
+            #[cfg(feature = "jxl")]
+            jxl_quality: 90,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// The metadata handlers embed in the output, empty if it is stripped.
+    pub fn metadata(&self) -> &Metadata {
+        if self.strip_metadata {
+            return &Metadata::EMPTY;
+        }
+        &self.metadata
+    }
+}
+
+/// An encoded output file.
+pub struct Encoded {
+    pub data: Vec<u8>,
+    /// File extension of the output, without the leading dot.
+    pub extension: &'static str,
+    /// MIME type of the output.
+    pub mime_type: &'static str,
+}
 
 pub trait ImfconvHandler {
-    fn exec(
+    /// Whether the format stores CMYK images.
+    fn supports_cmyk(&self) -> bool {
+        false
+    }
+
+    /// Whether the format stores an alpha channel.
+    fn supports_alpha(&self) -> bool {
+        false
+    }
+
+    /// File extension of the output, without the leading dot.
+    fn extension(&self) -> &'static str;
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>>;
+
+    /// Encode several images into one file.
+    ///
+    /// Formats without pages accept exactly one image and return an error otherwise.
+    fn encode_pages(
         &self,
-        width: u32,
-        height: u32,
-        raw_image: &[u8],
-        dest_filepath: &Path,
-    ) -> Result<(), Box<dyn Error>>;
+        images: &[DynamicImage],
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        match images {
+            [image] => self.encode(image, options),
+            _ => Err("the output format does not support multiple pages".into()),
+        }
+    }
+
+    /// Encode an animation, keeping its frames, delays and loop count.
+    ///
+    /// Formats without animation encode the first frame only.
+    fn encode_animation(
+        &self,
+        animation: &Animation,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        match animation.frames.first() {
+            Some(frame) => self.encode(&frame.image, options),
+            None => Err("the animation has no frames".into()),
+        }
+    }
+}
+
+/// Encode with the highest quality from 1 to `max_quality`, at most 100, whose output has at
+/// most `target_size` bytes.
+///
+/// The size is assumed to grow with the quality, so the quality is found by binary search.
+pub fn fit_quality<F>(
+    target_size: usize,
+    max_quality: u8,
+    mut encode: F,
+) -> Result<Vec<u8>, Box<dyn Error>>
+where
+    F: FnMut(u8) -> Result<Vec<u8>, Box<dyn Error>>,
+{
+    let (mut low, mut high) = (1, max_quality.clamp(1, 100));
+    let mut best = None;
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let data = encode(quality)?;
+        if data.len() <= target_size {
+            best = Some(data);
+            low = quality.saturating_add(1);
+        } else if quality == 1 {
+            break;
+        } else {
+            high = quality - 1;
+        }
+    }
+    match best {
+        Some(d) => Ok(d),
+        None => Err(format!(
+            "the output does not fit into {} bytes even at the lowest quality",
+            target_size
+        )
+        .into()),
+    }
+}
+
+/// Encode the image with one of the encoders of the `image` crate.
+pub fn write_to(
+    image: &DynamicImage,
+    format: ImageOutputFormat,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Cursor::new(Vec::new());
+    match image.write_to(&mut data, format) {
+        Ok(_) => Ok(data.into_inner()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Convert the image to 8 bit RGB for formats that store nothing else.
+///
+/// Float samples are linear light and get sRGB encoded, values above 1.0 are clipped.
+pub fn to_rgb8(image: &DynamicImage) -> RgbImage {
+    if !is_float(image) {
+        return image.to_rgb8();
+    }
+    to_srgb(image).to_rgb8()
+}
+
+/// Convert the image to 16 bit RGB, sRGB encoding float samples like `to_rgb8`.
+pub fn to_rgb16(image: &DynamicImage) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+    if !is_float(image) {
+        return image.to_rgb16();
+    }
+    to_srgb(image).to_rgb16()
+}
+
+/// Convert the image to 8 bit RGBA, sRGB encoding float samples like `to_rgb8`.
+pub fn to_rgba8(image: &DynamicImage) -> RgbaImage {
+    if !is_float(image) {
+        return image.to_rgba8();
+    }
+    to_srgb(image).to_rgba8()
+}
+
+/// Convert the image to 16 bit RGBA, sRGB encoding float samples like `to_rgb8`.
+pub fn to_rgba16(image: &DynamicImage) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+    if !is_float(image) {
+        return image.to_rgba16();
+    }
+    to_srgb(image).to_rgba16()
+}
+
+/// Convert the image to 8 bit RGBA if it has an alpha channel and to 8 bit RGB otherwise,
+/// for formats that store either.
+pub fn to_rgb8_or_rgba8(image: &DynamicImage) -> DynamicImage {
+    match image.color().has_alpha() {
+        true => DynamicImage::ImageRgba8(to_rgba8(image)),
+        false => DynamicImage::ImageRgb8(to_rgb8(image)),
+    }
+}
+
+/// sRGB encode the color channels of float images. Integer images are returned unchanged.
+pub fn to_srgb(image: &DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageRgb32F(i) => {
+            let mut encoded = i.clone();
+            for sample in encoded.iter_mut() {
+                *sample = srgb::encode(*sample);
+            }
+            DynamicImage::ImageRgb32F(encoded)
+        }
+        DynamicImage::ImageRgba32F(i) => {
+            let mut encoded = i.clone();
+            for pixel in encoded.pixels_mut() {
+                for sample in pixel.0[..3].iter_mut() {
+                    *sample = srgb::encode(*sample);
+                }
+            }
+            DynamicImage::ImageRgba32F(encoded)
+        }
+        _ => image.clone(),
+    }
+}
+
+/// Convert the image to linear light float RGB, decoding sRGB encoded integer samples.
+pub fn to_linear_rgb32f(image: &DynamicImage) -> Rgb32FImage {
+    let mut linear = image.to_rgb32f();
+    if !is_float(image) {
+        for sample in linear.iter_mut() {
+            *sample = srgb::decode(*sample);
+        }
+    }
+    linear
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fit_quality;
+
+    #[test]
+    fn fit_quality_stops_at_100() {
+        let mut qualities = Vec::new();
+        let data = fit_quality(usize::MAX, 255, |quality| {
+            qualities.push(quality);
+            Ok(vec![quality])
+        })
+        .unwrap();
+        assert_eq!(data, vec![100]);
+        assert!(qualities.iter().all(|&q| (1..=100).contains(&q)));
+    }
+
+    #[test]
+    fn fit_quality_finds_the_largest_fitting_quality() {
+        let data = fit_quality(42, 90, |quality| Ok(vec![0; quality as usize])).unwrap();
+        assert_eq!(data.len(), 42);
+        assert!(fit_quality(0, 90, |quality| Ok(vec![0; quality as usize])).is_err());
+    }
 }
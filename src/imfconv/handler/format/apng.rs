@@ -0,0 +1,119 @@
+use super::{
+    png::{embed_metadata, encoder},
+    to_rgb8, to_rgba8, EncodeOptions, Encoded, ImfconvHandler,
+};
+use crate::imfconv::handler::animation::{Animation, AnimationFrame};
+use image::DynamicImage;
+use png::{BitDepth, ColorType};
+use std::error::Error;
+
+/// Delay of frames written from a sequence of sources, in milliseconds.
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+
+pub struct ApngHandler;
+impl ImfconvHandler for ApngHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        self.encode_pages(std::slice::from_ref(image), options)
+    }
+
+    /// Every image becomes one frame of the animation.
+    fn encode_pages(
+        &self,
+        images: &[DynamicImage],
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let delay_ms = options.frame_delay_ms.unwrap_or(DEFAULT_FRAME_DELAY_MS);
+        let animation = Animation {
+            frames: images
+                .iter()
+                .map(|image| AnimationFrame {
+                    image: image.clone(),
+                    delay_ms,
+                })
+                .collect(),
+            loop_count: options.loop_count.unwrap_or(0),
+        };
+        self.encode_animation(&animation, options)
+    }
+
+    fn encode_animation(
+        &self,
+        animation: &Animation,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // Every frame shares the color type, so one transparent frame makes all of them RGBA.
+        let alpha = animation.frames.iter().any(|f| f.image.color().has_alpha());
+        let frames: Vec<_> = animation
+            .frames
+            .iter()
+            .map(|f| match alpha {
+                true => (DynamicImage::from(to_rgba8(&f.image)), f.delay_ms),
+                false => (DynamicImage::from(to_rgb8(&f.image)), f.delay_ms),
+            })
+            .collect();
+        let (width, height) = match frames.first() {
+            Some((f, _)) => (f.width(), f.height()),
+            None => return Err("the animation has no frames".into()),
+        };
+        if frames
+            .iter()
+            .any(|(f, _)| (f.width(), f.height()) != (width, height))
+        {
+            return Err("every frame of an APNG must have the same size".into());
+        }
+
+        let mut data = Vec::new();
+        let mut encoder = encoder(&mut data, width, height, options);
+        encoder.set_color(match alpha {
+            true => ColorType::Rgba,
+            false => ColorType::Rgb,
+        });
+        encoder.set_depth(BitDepth::Eight);
+        if let Err(e) = encoder.set_animated(frames.len() as u32, animation.loop_count.into()) {
+            return Err(Box::new(e));
+        }
+        let mut writer = match encoder.write_header() {
+            Ok(w) => w,
+            Err(e) => return Err(Box::new(e)),
+        };
+        for (image, delay_ms) in &frames {
+            let (numerator, denominator) = frame_delay(*delay_ms);
+            if let Err(e) = writer.set_frame_delay(numerator, denominator) {
+                return Err(Box::new(e));
+            }
+            if let Err(e) = writer.write_image_data(image.as_bytes()) {
+                return Err(Box::new(e));
+            }
+        }
+        if let Err(e) = writer.finish() {
+            return Err(Box::new(e));
+        }
+        Ok(Encoded {
+            data: embed_metadata(data, options)?,
+            extension: "png",
+            mime_type: "image/apng",
+        })
+    }
+}
+
+/// Express a delay in milliseconds as the 16 bit fraction of a second stored by APNG.
+///
+/// Delays too long for milliseconds fall back to hundredths of a second.
+fn frame_delay(delay_ms: u32) -> (u16, u16) {
+    match u16::try_from(delay_ms) {
+        Ok(ms) => (ms, 1000),
+        Err(_) => (u16::try_from(delay_ms / 10).unwrap_or(u16::MAX), 100),
+    }
+}
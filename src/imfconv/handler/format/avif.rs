@@ -0,0 +1,38 @@
+use super::{to_rgb8, EncodeOptions, Encoded, ImfconvHandler};
+use image::DynamicImage;
+use ravif::{Encoder, Img, RGB8};
+use std::error::Error;
+
+pub struct AvifHandler;
+impl ImfconvHandler for AvifHandler {
+    fn extension(&self) -> &'static str {
+        "avif"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_rgb8(image);
+        let (width, height) = raw_image.dimensions();
+        let pixels: Vec<RGB8> = raw_image
+            .chunks_exact(3)
+            .map(|p| RGB8::new(p[0], p[1], p[2]))
+            .collect();
+
+        // ravif panics on out of range values, so keep them inside the accepted bounds.
+        let encoder = Encoder::new()
+            .with_quality(f32::from(options.avif_quality.clamp(1, 100)))
+            .with_speed(options.avif_speed.clamp(1, 10));
+        let encoded = match encoder.encode_rgb(Img::new(&pixels, width as usize, height as usize)) {
+            Ok(e) => e,
+            Err(e) => return Err(Box::new(e)),
+        };
+        Ok(Encoded {
+            data: encoded.avif_file,
+            extension: "avif",
+            mime_type: "image/avif",
+        })
+    }
+}
@@ -0,0 +1,33 @@
+use super::{to_rgb8, write_to, EncodeOptions, Encoded, ImfconvHandler};
+use image::{DynamicImage, ImageOutputFormat};
+use std::error::Error;
+
+pub struct BmpHandler;
+impl ImfconvHandler for BmpHandler {
+    fn extension(&self) -> &'static str {
+        "bmp"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_rgb8(image);
+
+        let decoded_image = DynamicImage::from(raw_image);
+        let mut data = write_to(&decoded_image, ImageOutputFormat::Bmp)?;
+        if let Some(dpi) = options.dpi {
+            // The horizontal and vertical pixels per meter of the info header,
+            // which follows the 14 byte file header.
+            let ppm = ((dpi as f64 / 0.0254).round() as i32).to_le_bytes();
+            data[38..42].copy_from_slice(&ppm);
+            data[42..46].copy_from_slice(&ppm);
+        }
+        Ok(Encoded {
+            data,
+            extension: "bmp",
+            mime_type: "image/bmp",
+        })
+    }
+}
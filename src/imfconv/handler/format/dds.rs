@@ -0,0 +1,117 @@
+use super::{to_srgb, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::DdsCompression;
+use image::DynamicImage;
+use std::error::Error;
+use texpresso::{Format, Params};
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+
+pub struct DdsHandler;
+impl ImfconvHandler for DdsHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "dds"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_srgb(image).to_rgba8();
+        let (width, height) = raw_image.dimensions();
+
+        let format = match options.dds_compression {
+            DdsCompression::NONE => None,
+            DdsCompression::BC1 => Some(Format::Bc1),
+            DdsCompression::BC3 => Some(Format::Bc3),
+        };
+
+        let mut data = header(width, height, format);
+        match format {
+            None => {
+                // Uncompressed surfaces are stored as BGRA.
+                for pixel in raw_image.pixels() {
+                    let [r, g, b, a] = pixel.0;
+                    data.extend_from_slice(&[b, g, r, a]);
+                }
+            }
+            Some(f) => {
+                let (width, height) = (width as usize, height as usize);
+                let mut blocks = vec![0; f.compressed_size(width, height)];
+                f.compress(
+                    raw_image.as_raw(),
+                    width,
+                    height,
+                    Params::default(),
+                    &mut blocks,
+                );
+                data.extend_from_slice(&blocks);
+            }
+        }
+
+        Ok(Encoded {
+            data,
+            extension: "dds",
+            mime_type: "image/vnd-ms.dds",
+        })
+    }
+}
+
+/// Build the 128 byte DDS header, including the magic number.
+fn header(width: u32, height: u32, format: Option<Format>) -> Vec<u8> {
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+    let pitch_or_linear_size;
+    let pixel_format: [u32; 7];
+    match format {
+        None => {
+            flags |= DDSD_PITCH;
+            pitch_or_linear_size = width * 4;
+            pixel_format = [
+                DDPF_RGB | DDPF_ALPHAPIXELS,
+                0,
+                32,
+                0x00ff_0000,
+                0x0000_ff00,
+                0x0000_00ff,
+                0xff00_0000,
+            ];
+        }
+        Some(f) => {
+            let fourcc = match f {
+                Format::Bc1 => b"DXT1",
+                Format::Bc2 => b"DXT3",
+                Format::Bc3 => b"DXT5",
+                Format::Bc4 => b"ATI1",
+                Format::Bc5 => b"ATI2",
+            };
+            flags |= DDSD_LINEARSIZE;
+            pitch_or_linear_size = f.compressed_size(width as usize, height as usize) as u32;
+            pixel_format = [DDPF_FOURCC, u32::from_le_bytes(*fourcc), 0, 0, 0, 0, 0];
+        }
+    }
+
+    let mut fields = vec![124, flags, height, width, pitch_or_linear_size, 0, 0];
+    fields.extend_from_slice(&[0; 11]);
+    fields.push(32);
+    fields.extend_from_slice(&pixel_format);
+    fields.extend_from_slice(&[DDSCAPS_TEXTURE, 0, 0, 0, 0]);
+
+    let mut header = b"DDS ".to_vec();
+    for field in fields {
+        header.extend_from_slice(&field.to_le_bytes());
+    }
+    header
+}
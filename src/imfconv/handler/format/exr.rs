@@ -0,0 +1,26 @@
+use super::{to_linear_rgb32f, write_to, EncodeOptions, Encoded, ImfconvHandler};
+use image::{DynamicImage, ImageOutputFormat};
+use std::error::Error;
+
+pub struct ExrHandler;
+impl ImfconvHandler for ExrHandler {
+    fn extension(&self) -> &'static str {
+        "exr"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        _options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // OpenEXR stores linear light, so 8 bit sources are decoded from sRGB first.
+        let raw_image = to_linear_rgb32f(image);
+
+        let decoded_image = DynamicImage::from(raw_image);
+        Ok(Encoded {
+            data: write_to(&decoded_image, ImageOutputFormat::OpenExr)?,
+            extension: "exr",
+            mime_type: "image/x-exr",
+        })
+    }
+}
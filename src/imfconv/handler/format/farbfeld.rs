@@ -0,0 +1,39 @@
+use super::{to_srgb, EncodeOptions, Encoded, ImfconvHandler};
+use image::{codecs::farbfeld::FarbfeldEncoder, DynamicImage};
+use std::error::Error;
+
+pub struct FarbfeldHandler;
+impl ImfconvHandler for FarbfeldHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "ff"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        _options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // farbfeld always stores 16 bit RGBA samples.
+        let raw_image = to_srgb(image).to_rgba16();
+        let samples: Vec<u8> = raw_image
+            .as_raw()
+            .iter()
+            .flat_map(|s| s.to_ne_bytes())
+            .collect();
+
+        let mut data = Vec::new();
+        let encoder = FarbfeldEncoder::new(&mut data);
+        if let Err(e) = encoder.encode(&samples, raw_image.width(), raw_image.height()) {
+            return Err(Box::new(e));
+        }
+        Ok(Encoded {
+            data,
+            extension: "ff",
+            mime_type: "image/x-farbfeld",
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use super::{to_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::handler::palette::quantize;
+use image::DynamicImage;
+use std::{error::Error, fmt::Error as FmtError};
+
+/// GIF can index at most 256 colors per frame.
+const GIF_MAX_COLORS: usize = 256;
+
+pub struct GifHandler;
+impl ImfconvHandler for GifHandler {
+    fn extension(&self) -> &'static str {
+        "gif"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // Translucent images are flattened before they reach formats without alpha.
+        let raw_image = to_rgba8(image);
+        // GIF stores its dimensions as 16 bit values.
+        let (w, h) = match (
+            u16::try_from(raw_image.width()),
+            u16::try_from(raw_image.height()),
+        ) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => return Err(Box::new(FmtError)),
+        };
+
+        let quantized = quantize(&raw_image, GIF_MAX_COLORS, options.dither);
+
+        let mut data = Vec::new();
+        {
+            let mut encoder = match gif::Encoder::new(&mut data, w, h, &quantized.palette) {
+                Ok(e) => e,
+                Err(e) => return Err(Box::new(e)),
+            };
+            let frame = gif::Frame::from_indexed_pixels(w, h, &quantized.indices, None);
+            if let Err(e) = encoder.write_frame(&frame) {
+                return Err(Box::new(e));
+            }
+        }
+        Ok(Encoded {
+            data,
+            extension: "gif",
+            mime_type: "image/gif",
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use super::{to_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use image::{
+    codecs::ico::{IcoEncoder, IcoFrame},
+    imageops::{self, FilterType},
+    ColorType, DynamicImage, Rgba, RgbaImage,
+};
+use std::error::Error;
+
+pub struct IcoHandler;
+impl IcoHandler {
+    /// Scale the image to fit a `size` x `size` square and center it on a transparent canvas.
+    fn fit_square(image: &RgbaImage, size: u32) -> RgbaImage {
+        let (w, h) = image.dimensions();
+        let scale = size as f64 / w.max(h) as f64;
+        let scaled_w = ((w as f64 * scale).round() as u32).clamp(1, size);
+        let scaled_h = ((h as f64 * scale).round() as u32).clamp(1, size);
+        let scaled = imageops::resize(image, scaled_w, scaled_h, FilterType::Lanczos3);
+
+        let mut canvas = RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 0]));
+        imageops::overlay(
+            &mut canvas,
+            &scaled,
+            ((size - scaled_w) / 2) as i64,
+            ((size - scaled_h) / 2) as i64,
+        );
+        canvas
+    }
+}
+
+impl ImfconvHandler for IcoHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "ico"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_rgba8(image);
+
+        let mut frames = Vec::new();
+        for size in &options.ico_sizes {
+            let icon = Self::fit_square(&raw_image, *size);
+            match IcoFrame::as_png(&icon, *size, *size, ColorType::Rgba8) {
+                Ok(f) => frames.push(f),
+                Err(e) => return Err(Box::new(e)),
+            };
+        }
+
+        let mut data = Vec::new();
+        if let Err(e) = IcoEncoder::new(&mut data).encode_images(&frames) {
+            return Err(Box::new(e));
+        }
+        Ok(Encoded {
+            data,
+            extension: "ico",
+            mime_type: "image/x-icon",
+        })
+    }
+}
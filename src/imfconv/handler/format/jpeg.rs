@@ -1,25 +1,106 @@
-use super::ImfconvHandler;
-use std::{error::Error, fmt::Error as FmtError, path::Path};
+use super::{fit_quality, to_rgb8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::{
+    handler::metadata::{IPTC_RESOURCE_ID, PHOTOSHOP_JPEG_SIGNATURE, XMP_JPEG_NAMESPACE},
+    ChromaSubsampling,
+};
+use image::DynamicImage;
+use jpeg_encoder::{ColorType, Encoder, PixelDensity, SamplingFactor};
+use std::error::Error;
 
 pub struct JpegHandler;
 impl ImfconvHandler for JpegHandler {
-    fn exec(
+    fn supports_cmyk(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "jpeg"
+    }
+
+    fn encode(
         &self,
-        width: u32,
-        height: u32,
-        raw_image: &[u8],
-        dest_filepath: &Path,
-    ) -> Result<(), Box<dyn Error>> {
-        let raw_image = match image::RgbImage::from_vec(width, height, raw_image.to_vec()) {
-            Some(i) => i,
-            None => return Err(Box::new(FmtError)),
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let (samples, color) = match options.cmyk {
+            true => (image.to_rgba8().into_raw(), ColorType::Cmyk),
+            false => (to_rgb8(image).into_raw(), ColorType::Rgb),
+        };
+        let (width, height) = (image.width(), image.height());
+        let encode = |quality: u8| encode_jpeg(&samples, width, height, color, quality, options);
+        let data = match options.target_size_bytes {
+            Some(target_size) => fit_quality(target_size, options.jpeg_quality, encode)?,
+            None => encode(options.jpeg_quality)?,
         };
+        Ok(Encoded {
+            data,
+            extension: "jpeg",
+            mime_type: "image/jpeg",
+        })
+    }
+}
+
+fn encode_jpeg(
+    samples: &[u8],
+    width: u32,
+    height: u32,
+    color: ColorType,
+    quality: u8,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // JPEG stores its dimensions as 16 bit values.
+    let (w, h) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(w), Ok(h)) => (w, h),
+        _ => return Err("JPEG images must not exceed 65535 pixels per side".into()),
+    };
+
+    let mut data = Vec::new();
+    let mut encoder = Encoder::new(&mut data, quality);
+    encoder.set_sampling_factor(match options.jpeg_subsampling {
+        ChromaSubsampling::S444 => SamplingFactor::R_4_4_4,
+        ChromaSubsampling::S422 => SamplingFactor::R_4_2_2,
+        ChromaSubsampling::S420 => SamplingFactor::R_4_2_0,
+    });
+    encoder.set_progressive(options.jpeg_progressive);
+    encoder.set_optimized_huffman_tables(options.jpeg_optimize);
+    if let Some(dpi) = options.dpi {
+        encoder.set_density(PixelDensity::dpi(dpi.min(u16::MAX.into()) as u16));
+    }
+
+    let metadata = options.metadata();
+    let mut added = Ok(());
+    if let Some(exif) = &metadata.exif {
+        added = added.and(encoder.add_exif_metadata(exif));
+    }
+    if let Some(icc_profile) = &metadata.icc_profile {
+        added = added.and(encoder.add_icc_profile(icc_profile));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        added = added.and(encoder.add_app_segment(1, [XMP_JPEG_NAMESPACE, xmp].concat()));
+    }
+    if let Some(iptc) = &metadata.iptc {
+        added = added.and(encoder.add_app_segment(13, photoshop_resources(iptc)));
+    }
+    if let Err(e) = added {
+        return Err(Box::new(e));
+    }
+    match encoder.encode(samples, w, h, color) {
+        Ok(_) => Ok(data),
+        Err(e) => Err(Box::new(e)),
+    }
+}
 
-        let dest_filepath = dest_filepath.with_extension("jpeg");
-        let decoded_image = image::DynamicImage::from(raw_image);
-        match decoded_image.save(&dest_filepath) {
-            Ok(_) => return Ok(()),
-            Err(e) => return Err(Box::new(e)),
-        }
+/// Photoshop image resources holding the IPTC records `iptc`, as stored in APP13.
+fn photoshop_resources(iptc: &[u8]) -> Vec<u8> {
+    let mut data = PHOTOSHOP_JPEG_SIGNATURE.to_vec();
+    data.extend_from_slice(b"8BIM");
+    data.extend_from_slice(&IPTC_RESOURCE_ID.to_be_bytes());
+    // An empty name, padded to an even length.
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(&(iptc.len() as u32).to_be_bytes());
+    data.extend_from_slice(iptc);
+    if iptc.len() % 2 == 1 {
+        data.push(0);
     }
+    data
 }
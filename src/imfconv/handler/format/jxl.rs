@@ -0,0 +1,67 @@
+use super::{to_rgb8, EncodeOptions, Encoded, ImfconvHandler};
+use image::DynamicImage;
+use std::error::Error;
+use zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::EncoderOptions};
+use zune_jpegxl::JxlSimpleEncoder;
+
+pub struct JxlHandler;
+impl ImfconvHandler for JxlHandler {
+    fn extension(&self) -> &'static str {
+        "jxl"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // The pure Rust encoder only implements the lossless (modular) mode of JPEG XL.
+        if !options.jxl_lossless {
+            #[cfg(feature = "jxl-lossy")]
+            return encode_lossy(image, options);
+            #[cfg(not(feature = "jxl-lossy"))]
+            return Err("lossy JPEG XL output requires the jxl-lossy feature".into());
+        }
+
+        let raw_image = to_rgb8(image);
+        let encoder_options = EncoderOptions::new(
+            raw_image.width() as usize,
+            raw_image.height() as usize,
+            ColorSpace::RGB,
+            BitDepth::Eight,
+        );
+        let mut encoded = Vec::new();
+        match JxlSimpleEncoder::new(&raw_image, encoder_options).encode(&mut encoded) {
+            Ok(_) => {}
+            Err(e) => return Err(Box::new(e)),
+        };
+        Ok(Encoded {
+            data: encoded,
+            extension: "jxl",
+            mime_type: "image/jxl",
+        })
+    }
+}
+
+/// Encode `image` with libjxl at the JPEG style quality of `options`.
+#[cfg(feature = "jxl-lossy")]
+fn encode_lossy(image: &DynamicImage, options: &EncodeOptions) -> Result<Encoded, Box<dyn Error>> {
+    let raw_image = to_rgb8(image);
+    let mut encoder = match jpegxl_rs::encoder_builder()
+        .lossless(false)
+        .jpeg_quality(f32::from(options.jxl_quality.min(100)))
+        .build()
+    {
+        Ok(e) => e,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let encoded = match encoder.encode::<u8>(&raw_image, raw_image.width(), raw_image.height()) {
+        Ok(e) => e,
+        Err(e) => return Err(Box::new(e)),
+    };
+    Ok(Encoded {
+        data: encoded,
+        extension: "jxl",
+        mime_type: "image/jxl",
+    })
+}
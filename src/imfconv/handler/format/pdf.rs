@@ -0,0 +1,207 @@
+use super::{to_srgb, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::PdfCompression;
+use flate2::{write::ZlibEncoder, Compression};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage};
+use std::{error::Error, io::Write};
+
+pub struct PdfHandler;
+impl ImfconvHandler for PdfHandler {
+    fn extension(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        // Grayscale images stay single channel, everything else is embedded as RGB.
+        let raw_image = match image {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                DynamicImage::ImageLuma8(image.to_luma8())
+            }
+            _ => DynamicImage::from(to_srgb(image).to_rgb8()),
+        };
+        let (width, height) = (raw_image.width(), raw_image.height());
+        let color_space = match raw_image {
+            DynamicImage::ImageLuma8(_) => "/DeviceGray",
+            _ => "/DeviceRGB",
+        };
+
+        let (filter, stream) = match options.pdf_compression {
+            PdfCompression::JPEG => {
+                let mut stream = Vec::new();
+                if let Err(e) = JpegEncoder::new_with_quality(&mut stream, options.jpeg_quality)
+                    .encode_image(&raw_image)
+                {
+                    return Err(Box::new(e));
+                }
+                ("/DCTDecode", stream)
+            }
+            PdfCompression::FLATE => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                if let Err(e) = encoder.write_all(raw_image.as_bytes()) {
+                    return Err(Box::new(e));
+                }
+                match encoder.finish() {
+                    Ok(s) => ("/FlateDecode", s),
+                    Err(e) => return Err(Box::new(e)),
+                }
+            }
+        };
+
+        // A point is 1/72 inch, so without a resolution one pixel is one point.
+        let dpi = options.dpi.unwrap_or(72) as f64;
+        let (page_width, page_height) = (width as f64 * 72.0 / dpi, height as f64 * 72.0 / dpi);
+        let contents = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", page_width, page_height);
+        let mut pdf = PdfWriter::new();
+        pdf.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+        pdf.object(b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>");
+        pdf.object(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>",
+                page_width, page_height
+            )
+            .as_bytes(),
+        );
+        pdf.stream(
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace {} /BitsPerComponent 8 /Filter {}",
+                width, height, color_space, filter
+            ),
+            &stream,
+        );
+        pdf.stream("", contents.as_bytes());
+
+        Ok(Encoded {
+            data: pdf.finish(),
+            extension: "pdf",
+            mime_type: "application/pdf",
+        })
+    }
+}
+
+/// Writes numbered objects and keeps their offsets for the cross-reference table.
+struct PdfWriter {
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> Self {
+        Self {
+            data: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+            offsets: Vec::new(),
+        }
+    }
+
+    fn object(&mut self, body: &[u8]) {
+        self.offsets.push(self.data.len());
+        self.data
+            .extend_from_slice(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+        self.data.extend_from_slice(body);
+        self.data.extend_from_slice(b"\nendobj\n");
+    }
+
+    /// Write a stream object, `dict` holds the dictionary entries besides `/Length`.
+    fn stream(&mut self, dict: &str, stream: &[u8]) {
+        let mut body = format!("<< {} /Length {} >>\nstream\n", dict, stream.len()).into_bytes();
+        body.extend_from_slice(stream);
+        body.extend_from_slice(b"\nendstream");
+        self.object(&body);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.data.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            xref
+        ));
+        self.data.extend_from_slice(table.as_bytes());
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::read::ZlibDecoder;
+    use image::{DynamicImage, RgbImage};
+    use std::io::Read;
+
+    use super::{EncodeOptions, ImfconvHandler, PdfHandler, PdfWriter};
+    use crate::imfconv::PdfCompression;
+
+    /// The position of the first occurrence of `needle` in `data` at or after `start`.
+    fn find(data: &[u8], needle: &[u8], start: usize) -> usize {
+        start
+            + data[start..]
+                .windows(needle.len())
+                .position(|w| w == needle)
+                .unwrap()
+    }
+
+    #[test]
+    fn cross_reference_table_points_to_the_objects() {
+        let mut pdf = PdfWriter::new();
+        pdf.object(b"<< /Type /Catalog >>");
+        pdf.stream("/Filter /None", b"abc");
+        let data = pdf.finish();
+
+        let text = String::from_utf8_lossy(&data);
+        let startxref = text.rsplit("startxref\n").next().unwrap();
+        let xref: usize = startxref.lines().next().unwrap().parse().unwrap();
+        assert!(data[xref..].starts_with(b"xref\n0 3\n"));
+        let offsets: Vec<usize> = std::str::from_utf8(&data[xref..])
+            .unwrap()
+            .lines()
+            .skip(3)
+            .take(2)
+            .map(|entry| entry[..10].parse().unwrap())
+            .collect();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(data[*offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()));
+        }
+        assert!(data[offsets[1]..]
+            .starts_with(b"2 0 obj\n<< /Filter /None /Length 3 >>\nstream\nabc\nendstream"));
+        assert!(text.contains("trailer\n<< /Size 3 /Root 1 0 R >>\nstartxref\n"));
+        assert!(text.ends_with("\n%%EOF\n"));
+    }
+
+    #[test]
+    fn pages_embed_the_image_at_its_resolution() {
+        let image = RgbImage::from_fn(6, 4, |x, y| [(x * 40) as u8, (y * 60) as u8, 7].into());
+        let options = EncodeOptions {
+            dpi: Some(144),
+            pdf_compression: PdfCompression::FLATE,
+            ..EncodeOptions::default()
+        };
+        let encoded = PdfHandler
+            .encode(&DynamicImage::ImageRgb8(image.clone()), &options)
+            .unwrap();
+        let data = encoded.data;
+        assert!(String::from_utf8_lossy(&data).contains("/MediaBox [0 0 3 2]"));
+
+        let dict = find(&data, b"/Subtype /Image", 0);
+        let length = find(&data, b"/Length ", dict) + b"/Length ".len();
+        let end = find(&data, b" >>", length);
+        let length: usize = std::str::from_utf8(&data[length..end])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let start = find(&data, b"stream\n", end) + b"stream\n".len();
+        assert!(data[start + length..].starts_with(b"\nendstream"));
+
+        let mut samples = Vec::new();
+        ZlibDecoder::new(&data[start..start + length])
+            .read_to_end(&mut samples)
+            .unwrap();
+        assert_eq!(samples, image.into_raw());
+    }
+}
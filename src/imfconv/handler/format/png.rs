@@ -1,25 +1,469 @@
-use super::ImfconvHandler;
-use std::{error::Error, fmt::Error as FmtError, path::Path};
+use super::{to_rgb16, to_rgb8, to_rgba16, to_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::{handler::palette::quantize, BitDepth, PngCompression, PngFilter};
+use flate2::{write::ZlibEncoder, Crc};
+use image::DynamicImage;
+use png::{
+    AdaptiveFilterType, BitDepth as PngBitDepth, ColorType, Compression, Encoder, FilterType,
+};
+use std::{error::Error, io::Write};
+
+mod adam7;
 
 pub struct PngHandler;
 impl ImfconvHandler for PngHandler {
-    fn exec(
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn encode(
         &self,
-        width: u32,
-        height: u32,
-        raw_image: &[u8],
-        dest_filepath: &Path,
-    ) -> Result<(), Box<dyn Error>> {
-        let raw_image = match image::RgbImage::from_vec(width, height, raw_image.to_vec()) {
-            Some(i) => i,
-            None => return Err(Box::new(FmtError)),
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let (width, height) = (image.width(), image.height());
+        // Palette and the alpha of its entries, which is empty for opaque palettes.
+        let mut palette = None;
+        let gray_alpha = matches!(
+            image,
+            DynamicImage::ImageLumaA8(_) | DynamicImage::ImageLumaA16(_)
+        );
+        let rgba = image.color().has_alpha() && !gray_alpha;
+        let (samples, color, depth) = match (options.png_palette_colors, options.bit_depth) {
+            (Some(colors), _) => {
+                let quantized = quantize(&to_rgba8(image), colors, options.dither);
+                // Small palettes pack several indices into one byte.
+                let depth = match quantized.palette.len() / 3 {
+                    0..=2 => PngBitDepth::One,
+                    3..=4 => PngBitDepth::Two,
+                    5..=16 => PngBitDepth::Four,
+                    _ => PngBitDepth::Eight,
+                };
+                let alpha = match quantized.is_translucent() {
+                    true => trns(&quantized.alpha),
+                    false => Vec::new(),
+                };
+                palette = Some((quantized.palette, alpha));
+                (
+                    indexed_samples(quantized.indices, width, depth, options),
+                    ColorType::Indexed,
+                    depth,
+                )
+            }
+            // The bilevel profile leaves only black and white, one bit per pixel is enough.
+            (None, _) if options.bilevel => {
+                let indices: Vec<u8> = image.to_luma8().iter().map(|l| (*l > 127) as u8).collect();
+                palette = Some((vec![0, 0, 0, 255, 255, 255], Vec::new()));
+                (
+                    indexed_samples(indices, width, PngBitDepth::One, options),
+                    ColorType::Indexed,
+                    PngBitDepth::One,
+                )
+            }
+            (None, BitDepth::EIGHT) if gray_alpha => (
+                image.to_luma_alpha8().into_raw(),
+                ColorType::GrayscaleAlpha,
+                PngBitDepth::Eight,
+            ),
+            (None, BitDepth::SIXTEEN) if gray_alpha => (
+                image
+                    .to_luma_alpha16()
+                    .iter()
+                    .flat_map(|s| s.to_be_bytes())
+                    .collect(),
+                ColorType::GrayscaleAlpha,
+                PngBitDepth::Sixteen,
+            ),
+            (None, BitDepth::EIGHT) if rgba => (
+                to_rgba8(image).into_raw(),
+                ColorType::Rgba,
+                PngBitDepth::Eight,
+            ),
+            (None, BitDepth::SIXTEEN) if rgba => (
+                to_rgba16(image)
+                    .iter()
+                    .flat_map(|s| s.to_be_bytes())
+                    .collect(),
+                ColorType::Rgba,
+                PngBitDepth::Sixteen,
+            ),
+            (None, BitDepth::EIGHT) => (
+                to_rgb8(image).into_raw(),
+                ColorType::Rgb,
+                PngBitDepth::Eight,
+            ),
+            // PNG stores 16 bit samples in big endian byte order.
+            (None, BitDepth::SIXTEEN) => (
+                to_rgb16(image)
+                    .iter()
+                    .flat_map(|s| s.to_be_bytes())
+                    .collect(),
+                ColorType::Rgb,
+                PngBitDepth::Sixteen,
+            ),
         };
+        if options.png_interlace {
+            let data = adam7::encode(
+                &samples,
+                width,
+                height,
+                color,
+                depth as u8,
+                palette.as_ref().map(|(p, a)| (&p[..], &a[..])),
+                options,
+            )?;
+            return Ok(Encoded {
+                data: embed_metadata(data, options)?,
+                extension: "png",
+                mime_type: "image/png",
+            });
+        }
 
-        let dest_filepath = dest_filepath.with_extension("png");
-        let decoded_image = image::DynamicImage::from(raw_image);
-        match decoded_image.save(&dest_filepath) {
-            Ok(_) => return Ok(()),
+        let mut data = Vec::new();
+        let mut encoder = encoder(&mut data, width, height, options);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some((p, alpha)) = palette {
+            encoder.set_palette(p);
+            if !alpha.is_empty() {
+                encoder.set_trns(alpha);
+            }
+        }
+        let mut writer = match encoder.write_header() {
+            Ok(w) => w,
             Err(e) => return Err(Box::new(e)),
+        };
+        if let Err(e) = writer.write_image_data(&samples) {
+            return Err(Box::new(e));
         }
+        if let Err(e) = writer.finish() {
+            return Err(Box::new(e));
+        }
+        Ok(Encoded {
+            data: embed_metadata(data, options)?,
+            extension: "png",
+            mime_type: "image/png",
+        })
+    }
+}
+
+/// Insert the metadata and resolution chunks right after the IHDR chunk of the PNG file
+/// in `data`, where they precede the palette and the image data as required.
+pub fn embed_metadata(data: Vec<u8>, options: &EncodeOptions) -> Result<Vec<u8>, Box<dyn Error>> {
+    let metadata = options.metadata();
+    let mut chunks = Vec::new();
+    if let Some(dpi) = options.dpi {
+        // pHYs stores pixels per meter, unit 1 is the meter.
+        let ppm = ((dpi as f64 / 0.0254).round() as u32).to_be_bytes();
+        chunk(&mut chunks, b"pHYs", &[&ppm[..], &ppm, &[1]].concat());
+    }
+    if let Some(icc_profile) = &metadata.icc_profile {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        if let Err(e) = encoder.write_all(icc_profile) {
+            return Err(Box::new(e));
+        }
+        let compressed = match encoder.finish() {
+            Ok(c) => c,
+            Err(e) => return Err(Box::new(e)),
+        };
+        // Profile name, then compression method 0 (deflate).
+        chunk(
+            &mut chunks,
+            b"iCCP",
+            &[b"ICC Profile\0\0", &compressed[..]].concat(),
+        );
+    }
+    if let Some(exif) = &metadata.exif {
+        chunk(&mut chunks, b"eXIf", exif);
+    }
+    if let Some(xmp) = &metadata.xmp {
+        // Uncompressed international text without language and translated keyword.
+        chunk(
+            &mut chunks,
+            b"iTXt",
+            &[b"XML:com.adobe.xmp\0\0\0\0\0", &xmp[..]].concat(),
+        );
+    }
+    if chunks.is_empty() {
+        return Ok(data);
+    }
+
+    // The signature (8 bytes) and IHDR (length, type, 13 bytes of data and CRC) come first.
+    let end_of_ihdr = 8 + 4 + 4 + 13 + 4;
+    let mut embedded = Vec::with_capacity(data.len() + chunks.len());
+    embedded.extend_from_slice(&data[..end_of_ihdr]);
+    embedded.extend_from_slice(&chunks);
+    embedded.extend_from_slice(&data[end_of_ihdr..]);
+    Ok(embedded)
+}
+
+/// Append a chunk with its length and CRC to `data`.
+fn chunk(data: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(body);
+    data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// The tRNS chunk of a palette with the `alpha` of its entries, leaving out the opaque
+/// entries at the end, which the chunk may omit.
+fn trns(alpha: &[u8]) -> Vec<u8> {
+    let length = alpha.iter().rposition(|a| *a < 255).map_or(0, |i| i + 1);
+    alpha[..length].to_vec()
+}
+
+/// The samples of an indexed image with one palette index per pixel in `indices`.
+///
+/// Rows are packed to `depth` bits per sample, interlaced images leave the packing of the
+/// rows of every pass to `adam7::encode`.
+fn indexed_samples(
+    indices: Vec<u8>,
+    width: u32,
+    depth: PngBitDepth,
+    options: &EncodeOptions,
+) -> Vec<u8> {
+    match options.png_interlace {
+        true => indices,
+        false => pack_indices(&indices, width, depth as u8),
+    }
+}
+
+/// Pack one palette index per pixel into rows of `depth` bit samples.
+fn pack_indices(indices: &[u8], width: u32, depth: u8) -> Vec<u8> {
+    if depth == 8 {
+        return indices.to_vec();
+    }
+    let depth = usize::from(depth);
+    indices
+        .chunks(width as usize)
+        .flat_map(|row| {
+            row.chunks(8 / depth).map(|c| {
+                c.iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, index)| byte | index << (8 - depth * (i + 1)))
+            })
+        })
+        .collect()
+}
+
+/// A PNG encoder using the compression level and filter of `options`.
+pub fn encoder<W: Write>(
+    w: W,
+    width: u32,
+    height: u32,
+    options: &EncodeOptions,
+) -> Encoder<'static, W> {
+    let mut encoder = Encoder::new(w, width, height);
+    encoder.set_compression(match options.png_compression {
+        PngCompression::FAST => Compression::Fast,
+        PngCompression::DEFAULT => Compression::Default,
+        PngCompression::BEST => Compression::Best,
+    });
+    let (filter, adaptive) = match options.png_filter {
+        PngFilter::NONE => (FilterType::NoFilter, AdaptiveFilterType::NonAdaptive),
+        PngFilter::SUB => (FilterType::Sub, AdaptiveFilterType::NonAdaptive),
+        PngFilter::UP => (FilterType::Up, AdaptiveFilterType::NonAdaptive),
+        PngFilter::AVG => (FilterType::Avg, AdaptiveFilterType::NonAdaptive),
+        PngFilter::PAETH => (FilterType::Paeth, AdaptiveFilterType::NonAdaptive),
+        PngFilter::ADAPTIVE => (FilterType::Sub, AdaptiveFilterType::Adaptive),
+    };
+    encoder.set_filter(filter);
+    encoder.set_adaptive_filter(adaptive);
+    encoder
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, Rgb32FImage, Rgba, RgbaImage};
+    use png::{BitDepth, ColorType, Decoder, Transformations};
+
+    use super::{pack_indices, to_rgb16, to_rgb8, EncodeOptions, ImfconvHandler, PngHandler};
+    use crate::imfconv::BitDepth as ImfconvBitDepth;
+
+    /// An icon with a transparent background and a translucent edge.
+    fn icon() -> RgbaImage {
+        RgbaImage::from_fn(9, 7, |x, y| match (x, y) {
+            (2..=6, 2..=4) => Rgba([200, 30, 30, 255]),
+            (1..=7, 1..=5) => Rgba([200, 30, 30, 128]),
+            // Hidden colors that must not become visible.
+            _ => Rgba([(x * 25) as u8, (y * 30) as u8, 99, 0]),
+        })
+    }
+
+    /// Decode the PNG file `data`, expanding palettes, to its color type and RGBA samples.
+    fn decode(data: &[u8]) -> (ColorType, bool, RgbaImage) {
+        let (color, _, interlaced, image) = decode_image(data);
+        (color, interlaced, image.to_rgba8())
+    }
+
+    /// Decode the PNG file `data`, expanding palettes, to its color type, bit depth and image.
+    fn decode_image(data: &[u8]) -> (ColorType, BitDepth, bool, DynamicImage) {
+        let mut decoder = Decoder::new(data);
+        decoder.set_transformations(Transformations::EXPAND);
+        let mut reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        let (color, depth, interlaced) = (info.color_type, info.bit_depth, info.interlaced);
+        let mut samples = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut samples).unwrap();
+        samples.truncate(frame.buffer_size());
+        let (width, height) = (frame.width, frame.height);
+        let image = match (frame.color_type, frame.bit_depth) {
+            (ColorType::Rgb, BitDepth::Sixteen) => {
+                let samples = samples
+                    .chunks(2)
+                    .map(|s| u16::from_be_bytes([s[0], s[1]]))
+                    .collect();
+                DynamicImage::ImageRgb16(
+                    image::ImageBuffer::from_raw(width, height, samples).unwrap(),
+                )
+            }
+            (ColorType::Rgb, _) => {
+                DynamicImage::ImageRgb8(image::RgbImage::from_raw(width, height, samples).unwrap())
+            }
+            _ => DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, samples).unwrap()),
+        };
+        (color, depth, interlaced, image)
+    }
+
+    #[test]
+    fn indices_are_packed_from_the_high_bits() {
+        assert_eq!(
+            pack_indices(&[1, 0, 1, 1, 0, 0, 0, 1, 1], 9, 1),
+            [0b1011_0001, 0b1000_0000]
+        );
+        assert_eq!(
+            pack_indices(&[3, 1, 2, 0, 1], 5, 2),
+            [0b1101_1000, 0b0100_0000]
+        );
+        // Every row starts on a new byte.
+        assert_eq!(
+            pack_indices(&[15, 1, 2, 3, 4, 5], 3, 4),
+            [0xf1, 0x20, 0x34, 0x50]
+        );
+    }
+
+    #[test]
+    fn interlaced_palettes_keep_their_bit_depth() {
+        for (colors, depth) in [
+            (2, BitDepth::One),
+            (4, BitDepth::Two),
+            (16, BitDepth::Four),
+            (40, BitDepth::Eight),
+        ] {
+            // Odd sizes leave partial bytes at the end of the rows of every pass.
+            let image = RgbaImage::from_fn(13, 11, |x, y| {
+                let i = (x * 7 + y * 3) % colors;
+                Rgba([(i * 6) as u8, 255 - (i * 6) as u8, (i % 3 * 100) as u8, 255])
+            });
+            for interlace in [false, true] {
+                let options = EncodeOptions {
+                    png_palette_colors: Some(colors as usize),
+                    png_interlace: interlace,
+                    ..EncodeOptions::default()
+                };
+                let encoded = PngHandler
+                    .encode(&DynamicImage::ImageRgba8(image.clone()), &options)
+                    .unwrap();
+                let (color, decoded_depth, interlaced, decoded) = decode_image(&encoded.data);
+                assert_eq!(
+                    (color, decoded_depth, interlaced),
+                    (ColorType::Indexed, depth, interlace)
+                );
+                assert_eq!(decoded.to_rgba8(), image);
+            }
+        }
+    }
+
+    #[test]
+    fn interlaced_bilevel_images_have_one_bit() {
+        let image = Rgb32FImage::from_fn(10, 9, |x, y| match (x + y) % 3 {
+            0 => Rgb([1.0, 1.0, 1.0]),
+            _ => Rgb([0.0, 0.0, 0.0]),
+        });
+        let options = EncodeOptions {
+            bilevel: true,
+            png_interlace: true,
+            ..EncodeOptions::default()
+        };
+        let encoded = PngHandler
+            .encode(&DynamicImage::ImageRgb32F(image.clone()), &options)
+            .unwrap();
+        let (color, depth, _, decoded) = decode_image(&encoded.data);
+        assert_eq!((color, depth), (ColorType::Indexed, BitDepth::One));
+        assert_eq!(
+            decoded.to_rgb8(),
+            DynamicImage::ImageRgb32F(image).to_rgb8()
+        );
+    }
+
+    #[test]
+    fn interlaced_images_round_trip() {
+        let image = Rgb32FImage::from_fn(17, 12, |x, y| {
+            Rgb([x as f32 / 16.0, y as f32 / 11.0, ((x * y) % 5) as f32 / 4.0])
+        });
+        let image = DynamicImage::ImageRgb32F(image);
+        for bit_depth in [ImfconvBitDepth::EIGHT, ImfconvBitDepth::SIXTEEN] {
+            let options = EncodeOptions {
+                bit_depth,
+                png_interlace: true,
+                ..EncodeOptions::default()
+            };
+            let encoded = PngHandler.encode(&image, &options).unwrap();
+            let (_, depth, interlaced, decoded) = decode_image(&encoded.data);
+            assert!(interlaced);
+            match bit_depth {
+                ImfconvBitDepth::EIGHT => {
+                    assert_eq!(depth, BitDepth::Eight);
+                    assert_eq!(decoded.to_rgb8(), to_rgb8(&image));
+                }
+                ImfconvBitDepth::SIXTEEN => {
+                    assert_eq!(depth, BitDepth::Sixteen);
+                    assert_eq!(decoded.to_rgb16(), to_rgb16(&image));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn palettes_keep_transparency() {
+        let image = DynamicImage::ImageRgba8(icon());
+        for interlace in [false, true] {
+            let options = EncodeOptions {
+                png_palette_colors: Some(16),
+                png_interlace: interlace,
+                ..EncodeOptions::default()
+            };
+            let encoded = PngHandler.encode(&image, &options).unwrap();
+            let (color, interlaced, decoded) = decode(&encoded.data);
+            assert_eq!(color, ColorType::Indexed);
+            assert_eq!(interlaced, interlace);
+            assert_eq!(*decoded.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+            assert_eq!(*decoded.get_pixel(1, 1), Rgba([200, 30, 30, 128]));
+            assert_eq!(*decoded.get_pixel(4, 3), Rgba([200, 30, 30, 255]));
+        }
+    }
+
+    #[test]
+    fn quantized_palettes_keep_transparency() {
+        let image = RgbaImage::from_fn(64, 64, |x, y| match x < 32 {
+            true => Rgba([(x * 8) as u8, (y * 4) as u8, 128, 255]),
+            false => Rgba([(y * 4) as u8, 255, 0, 0]),
+        });
+        let options = EncodeOptions {
+            png_palette_colors: Some(16),
+            ..EncodeOptions::default()
+        };
+        let encoded = PngHandler
+            .encode(&DynamicImage::ImageRgba8(image), &options)
+            .unwrap();
+        let (_, _, decoded) = decode(&encoded.data);
+        assert_eq!(decoded.get_pixel(40, 10).0[3], 0);
+        assert_eq!(decoded.get_pixel(10, 10).0[3], 255);
     }
 }
@@ -0,0 +1,177 @@
+use super::{chunk, pack_indices, EncodeOptions};
+use crate::imfconv::{PngCompression, PngFilter};
+use flate2::{write::ZlibEncoder, Compression};
+use png::ColorType;
+use std::{error::Error, io::Write};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// First pixel and spacing (x, y, dx, dy) of each of the seven Adam7 passes.
+const PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Encode an Adam7 interlaced PNG, which the `png` crate can only decode.
+///
+/// `samples` have `bit_depth` bits each, 16 bit samples in big endian. Indexed images with
+/// 1, 2 or 4 bit samples pass one unpacked index per byte, which is packed per pass row.
+/// Indexed images are written with their `palette` of packed RGB triples and the alpha of
+/// its entries, which is empty for opaque palettes.
+pub fn encode(
+    samples: &[u8],
+    width: u32,
+    height: u32,
+    color: ColorType,
+    bit_depth: u8,
+    palette: Option<(&[u8], &[u8])>,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // The filters work on whole bytes, sub-byte samples count as one byte.
+    let bytes_per_pixel = (color.samples() * usize::from(bit_depth) / 8).max(1);
+    let unpacked = match bit_depth < 8 {
+        true => 1,
+        false => bytes_per_pixel,
+    };
+
+    // Every pass is a reduced image of its own, filtered independently of the others.
+    let mut filtered = Vec::new();
+    for (x0, y0, dx, dy) in PASSES {
+        if width <= x0 || height <= y0 {
+            continue;
+        }
+        let mut previous = Vec::new();
+        for y in (y0..height).step_by(dy as usize) {
+            let row: Vec<u8> = (x0..width)
+                .step_by(dx as usize)
+                .flat_map(|x| {
+                    let offset = (y as usize * width as usize + x as usize) * unpacked;
+                    &samples[offset..offset + unpacked]
+                })
+                .copied()
+                .collect();
+            let row = match bit_depth < 8 {
+                true => pack_indices(&row, row.len() as u32, bit_depth),
+                false => row,
+            };
+            if previous.is_empty() {
+                previous = vec![0; row.len()];
+            }
+            filter_row(
+                &row,
+                &previous,
+                bytes_per_pixel,
+                options.png_filter,
+                &mut filtered,
+            );
+            previous = row;
+        }
+    }
+
+    let level = match options.png_compression {
+        PngCompression::FAST => Compression::fast(),
+        PngCompression::DEFAULT => Compression::default(),
+        PngCompression::BEST => Compression::best(),
+    };
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    if let Err(e) = encoder.write_all(&filtered) {
+        return Err(Box::new(e));
+    }
+    let idat = match encoder.finish() {
+        Ok(d) => d,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Deflate, adaptive filtering and Adam7 interlacing.
+    ihdr.extend_from_slice(&[bit_depth, color as u8, 0, 0, 1]);
+
+    let mut data = SIGNATURE.to_vec();
+    chunk(&mut data, b"IHDR", &ihdr);
+    if let Some((p, alpha)) = palette {
+        chunk(&mut data, b"PLTE", p);
+        if !alpha.is_empty() {
+            chunk(&mut data, b"tRNS", alpha);
+        }
+    }
+    chunk(&mut data, b"IDAT", &idat);
+    chunk(&mut data, b"IEND", &[]);
+    Ok(data)
+}
+
+/// Append `row`, prefixed with its filter type, filtered against the `previous` row of its pass.
+fn filter_row(
+    row: &[u8],
+    previous: &[u8],
+    bytes_per_pixel: usize,
+    filter: PngFilter,
+    filtered: &mut Vec<u8>,
+) {
+    let filter_type = match filter {
+        PngFilter::NONE => 0,
+        PngFilter::SUB => 1,
+        PngFilter::UP => 2,
+        PngFilter::AVG => 3,
+        PngFilter::PAETH => 4,
+        // The heuristic of the PNG specification, the smallest sum of signed differences.
+        PngFilter::ADAPTIVE => (0..5)
+            .min_by_key(|t| {
+                apply_filter(*t, row, previous, bytes_per_pixel)
+                    .iter()
+                    .map(|b| u32::from((*b as i8).unsigned_abs()))
+                    .sum::<u32>()
+            })
+            .unwrap_or(0),
+    };
+    filtered.push(filter_type);
+    filtered.extend(apply_filter(filter_type, row, previous, bytes_per_pixel));
+}
+
+/// Filter `row` with the byte `bytes_per_pixel` to the left as the left neighbour.
+fn apply_filter(filter_type: u8, row: &[u8], previous: &[u8], bytes_per_pixel: usize) -> Vec<u8> {
+    (0..row.len())
+        .map(|i| {
+            let a = if i >= bytes_per_pixel {
+                row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let b = previous[i];
+            let c = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let prediction = match filter_type {
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => 0,
+            };
+            row[i].wrapping_sub(prediction)
+        })
+        .collect()
+}
+
+/// Whichever of the left, upper and upper left bytes is closest to `a + b - c`.
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
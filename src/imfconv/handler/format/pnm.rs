@@ -0,0 +1,55 @@
+use super::{to_rgb8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::PnmEncoding;
+use image::{
+    codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding},
+    ColorType, DynamicImage,
+};
+use std::error::Error;
+
+pub struct PnmHandler;
+impl ImfconvHandler for PnmHandler {
+    /// Graymaps of grayscale images are written as `pgm` instead.
+    fn extension(&self) -> &'static str {
+        "ppm"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let encoding = match options.pnm_encoding {
+            PnmEncoding::BINARY => SampleEncoding::Binary,
+            PnmEncoding::ASCII => SampleEncoding::Ascii,
+        };
+
+        // Grayscale images become graymaps (P5/P2), everything else pixmaps (P6/P3).
+        let (raw_image, subtype, color, extension, mime_type) = match image {
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => (
+                image.to_luma8().into_raw(),
+                PnmSubtype::Graymap(encoding),
+                ColorType::L8,
+                "pgm",
+                "image/x-portable-graymap",
+            ),
+            _ => (
+                to_rgb8(image).into_raw(),
+                PnmSubtype::Pixmap(encoding),
+                ColorType::Rgb8,
+                "ppm",
+                "image/x-portable-pixmap",
+            ),
+        };
+
+        let mut data = Vec::new();
+        let mut encoder = PnmEncoder::new(&mut data).with_subtype(subtype);
+        if let Err(e) = encoder.encode(raw_image.as_slice(), image.width(), image.height(), color) {
+            return Err(Box::new(e));
+        }
+        Ok(Encoded {
+            data,
+            extension,
+            mime_type,
+        })
+    }
+}
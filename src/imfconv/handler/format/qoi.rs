@@ -0,0 +1,32 @@
+use super::{to_rgb8_or_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use image::DynamicImage;
+use std::error::Error;
+
+pub struct QoiHandler;
+impl ImfconvHandler for QoiHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "qoi"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        _options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_rgb8_or_rgba8(image);
+        let encoded =
+            match qoi::encode_to_vec(raw_image.as_bytes(), raw_image.width(), raw_image.height()) {
+                Ok(e) => e,
+                Err(e) => return Err(Box::new(e)),
+            };
+        Ok(Encoded {
+            data: encoded,
+            extension: "qoi",
+            mime_type: "image/qoi",
+        })
+    }
+}
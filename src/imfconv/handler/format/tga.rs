@@ -0,0 +1,27 @@
+use super::{to_rgb8_or_rgba8, write_to, EncodeOptions, Encoded, ImfconvHandler};
+use image::{DynamicImage, ImageOutputFormat};
+use std::error::Error;
+
+pub struct TgaHandler;
+impl ImfconvHandler for TgaHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "tga"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        _options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let decoded_image = to_rgb8_or_rgba8(image);
+        Ok(Encoded {
+            data: write_to(&decoded_image, ImageOutputFormat::Tga)?,
+            extension: "tga",
+            mime_type: "image/x-tga",
+        })
+    }
+}
@@ -1,25 +1,541 @@
-use super::ImfconvHandler;
-use std::{error::Error, fmt::Error as FmtError, path::Path};
+use super::{to_rgb16, to_rgb8, to_rgba16, to_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::{handler::metadata::DESCRIPTIVE_TIFF_TAGS, BitDepth, TiffCompression};
+use exif::{Context, Exif, Field, In, Reader, Value};
+use fax::{encoder::Encoder, Color, VecWriter};
+use image::{DynamicImage, GrayImage};
+use std::{
+    error::Error,
+    io::{Cursor, Seek, Write},
+};
+use tiff::{
+    encoder::{
+        colortype::{ColorType, CMYK16, CMYK8, RGB16, RGB8, RGBA16, RGBA8},
+        compression::{Compression, Deflate, Lzw, Packbits, Uncompressed},
+        DirectoryEncoder, Rational, TiffEncoder, TiffKindStandard, TiffValue,
+    },
+    tags::{CompressionMethod, PhotometricInterpretation, ResolutionUnit, Tag},
+    TiffResult,
+};
+
+/// Tag holding the XMP packet of a page.
+const XMP: u16 = 700;
+/// Tag holding the IPTC-IIM records of a page.
+const IPTC: u16 = 33723;
+/// Tag holding the ICC profile of a page.
+const ICC_PROFILE: u16 = 34675;
+/// ExtraSamples value of a fourth sample holding unassociated alpha.
+const UNASSOCIATED_ALPHA: u16 = 2;
+/// Tags pointing from the page to the directories holding the EXIF and GPS fields.
+const SUB_DIRECTORIES: [(Context, u16); 2] = [(Context::Exif, 34665), (Context::Gps, 34853)];
 
 pub struct TiffHandler;
 impl ImfconvHandler for TiffHandler {
-    fn exec(
+    fn supports_cmyk(&self) -> bool {
+        true
+    }
+
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "tiff"
+    }
+
+    fn encode(
         &self,
-        width: u32,
-        height: u32,
-        raw_image: &[u8],
-        dest_filepath: &Path,
-    ) -> Result<(), Box<dyn Error>> {
-        let raw_image = match image::RgbImage::from_vec(width, height, raw_image.to_vec()) {
-            Some(i) => i,
-            None => return Err(Box::new(FmtError)),
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        self.encode_pages(std::slice::from_ref(image), options)
+    }
+
+    /// Every image becomes one page of a multi-page TIFF.
+    ///
+    /// The ICC profile is written to every page, EXIF, XMP and IPTC metadata
+    /// to the first page only.
+    fn encode_pages(
+        &self,
+        images: &[DynamicImage],
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let exif = match &options.metadata().exif {
+            Some(e) => Reader::new().read_raw(e.clone()).ok(),
+            None => None,
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut encoder = match TiffEncoder::new(&mut cursor) {
+                Ok(e) => e,
+                Err(e) => return Err(Box::new(e)),
+            };
+            for (i, image) in images.iter().enumerate() {
+                let (width, height) = (image.width(), image.height());
+                let compression = options.tiff_compression;
+                let metadata = options.metadata();
+                let tags = PageTags {
+                    exif: exif.as_ref().filter(|_| i == 0),
+                    xmp: metadata.xmp.as_deref().filter(|_| i == 0),
+                    iptc: metadata.iptc.as_deref().filter(|_| i == 0),
+                    icc_profile: metadata.icc_profile.as_deref(),
+                    dpi: options.dpi,
+                };
+                let alpha = image.color().has_alpha();
+                let written = match (options.cmyk, options.bit_depth) {
+                    _ if options.bilevel => {
+                        write_bilevel_page(&mut encoder, &image.to_luma8(), &tags)
+                    }
+                    (true, BitDepth::EIGHT) => write_page::<CMYK8, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &image.to_rgba8(),
+                        compression,
+                        &tags,
+                    ),
+                    (true, BitDepth::SIXTEEN) => write_page::<CMYK16, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &image.to_rgba16(),
+                        compression,
+                        &tags,
+                    ),
+                    (false, BitDepth::EIGHT) if alpha => write_page::<RGBA8, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &to_rgba8(image),
+                        compression,
+                        &tags,
+                    ),
+                    (false, BitDepth::SIXTEEN) if alpha => write_page::<RGBA16, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &to_rgba16(image),
+                        compression,
+                        &tags,
+                    ),
+                    (false, BitDepth::EIGHT) => write_page::<RGB8, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &to_rgb8(image),
+                        compression,
+                        &tags,
+                    ),
+                    (false, BitDepth::SIXTEEN) => write_page::<RGB16, _>(
+                        &mut encoder,
+                        width,
+                        height,
+                        &to_rgb16(image),
+                        compression,
+                        &tags,
+                    ),
+                };
+                if let Err(e) = written {
+                    return Err(Box::new(e));
+                }
+            }
+        }
+        Ok(Encoded {
+            data: cursor.into_inner(),
+            extension: "tiff",
+            mime_type: "image/tiff",
+        })
+    }
+}
+
+/// Metadata written to the directory of a page besides the image data.
+struct PageTags<'a> {
+    exif: Option<&'a Exif>,
+    xmp: Option<&'a [u8]>,
+    iptc: Option<&'a [u8]>,
+    icc_profile: Option<&'a [u8]>,
+    dpi: Option<u32>,
+}
+
+/// Write one page with the samples of color type `C`.
+fn write_page<C: ColorType, W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    samples: &[C::Inner],
+    compression: TiffCompression,
+    tags: &PageTags,
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    match compression {
+        TiffCompression::NONE => {
+            write_image::<C, _, _>(encoder, width, height, Uncompressed, samples, tags)
+        }
+        TiffCompression::LZW => write_image::<C, _, _>(encoder, width, height, Lzw, samples, tags),
+        TiffCompression::DEFLATE => {
+            write_image::<C, _, _>(encoder, width, height, Deflate::default(), samples, tags)
+        }
+        TiffCompression::PACKBITS => {
+            write_image::<C, _, _>(encoder, width, height, Packbits, samples, tags)
+        }
+    }
+}
+
+/// Write one page compressed with `compression`, along with its `tags`.
+fn write_image<C: ColorType, D: Compression, W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    compression: D,
+    samples: &[C::Inner],
+    tags: &PageTags,
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    let mut image = encoder.new_image_with_compression::<C, D>(width, height, compression)?;
+    // The `tiff` crate does not declare the fourth sample of RGBA pages itself.
+    if C::TIFF_VALUE == PhotometricInterpretation::RGB && C::BITS_PER_SAMPLE.len() == 4 {
+        image
+            .encoder()
+            .write_tag(Tag::ExtraSamples, UNASSOCIATED_ALPHA)?;
+    }
+    write_tags(image.encoder(), tags)?;
+    image.write_data(samples)
+}
+
+/// Write one black and white page with one bit per pixel, compressed with CCITT Group 4.
+///
+/// The `tiff` crate has no Group 4 compression, so the strip is encoded separately
+/// and the page directory is written field by field.
+fn write_bilevel_page<W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    image: &GrayImage,
+    tags: &PageTags,
+) -> TiffResult<()> {
+    let (width, height) = image.dimensions();
+    let mut fax = Encoder::new(VecWriter::new());
+    for row in image.rows() {
+        let pels = row.map(|p| match p.0[0] {
+            0 => Color::Black,
+            _ => Color::White,
+        });
+        // Writing to a `VecWriter` cannot fail.
+        let Ok(()) = fax.encode_line(pels, width);
+    }
+    let Ok(writer) = fax.finish();
+    let strip = writer.finish();
+
+    let mut directory = encoder.new_directory()?;
+    let offset = u32::try_from(directory.write_data(&strip[..])?)?;
+    directory.write_tag(Tag::ImageWidth, width)?;
+    directory.write_tag(Tag::ImageLength, height)?;
+    directory.write_tag(Tag::BitsPerSample, 1u16)?;
+    directory.write_tag(Tag::Compression, CompressionMethod::Fax4.to_u16())?;
+    directory.write_tag(
+        Tag::PhotometricInterpretation,
+        PhotometricInterpretation::WhiteIsZero.to_u16(),
+    )?;
+    directory.write_tag(Tag::StripOffsets, offset)?;
+    directory.write_tag(Tag::SamplesPerPixel, 1u16)?;
+    directory.write_tag(Tag::RowsPerStrip, height)?;
+    directory.write_tag(Tag::StripByteCounts, strip.len() as u32)?;
+    directory.write_tag(Tag::ResolutionUnit, ResolutionUnit::None.to_u16())?;
+    directory.write_tag(Tag::XResolution, Rational { n: 1, d: 1 })?;
+    directory.write_tag(Tag::YResolution, Rational { n: 1, d: 1 })?;
+    write_tags(&mut directory, tags)?;
+    directory.finish()
+}
+
+/// Write the metadata `tags` to the directory of a page.
+fn write_tags<W: Write + Seek>(
+    directory: &mut DirectoryEncoder<W, TiffKindStandard>,
+    tags: &PageTags,
+) -> TiffResult<()> {
+    for (tag, data) in [
+        (XMP, tags.xmp),
+        (IPTC, tags.iptc),
+        (ICC_PROFILE, tags.icc_profile),
+    ] {
+        if let Some(data) = data {
+            directory.write_tag(Tag::Unknown(tag), data)?;
+        }
+    }
+    if let Some(exif) = tags.exif {
+        write_exif(directory, exif)?;
+    }
+    // Written after the EXIF fields, which may carry a resolution of their own.
+    if let Some(dpi) = tags.dpi {
+        directory.write_tag(Tag::ResolutionUnit, ResolutionUnit::Inch.to_u16())?;
+        directory.write_tag(Tag::XResolution, Rational { n: dpi, d: 1 })?;
+        directory.write_tag(Tag::YResolution, Rational { n: dpi, d: 1 })?;
+    }
+    Ok(())
+}
+
+/// Write the fields of `exif` to the directory of a page.
+///
+/// The descriptive fields of the primary directory become tags of the page itself,
+/// the EXIF and GPS fields are written to their own directories referenced by the page.
+/// This has to happen before the samples are written, as the samples are compressed
+/// by the same writer.
+fn write_exif<W: Write + Seek>(
+    directory: &mut DirectoryEncoder<W, TiffKindStandard>,
+    exif: &Exif,
+) -> TiffResult<()> {
+    let primary: Vec<&Field> = exif.fields().filter(|f| f.ifd_num == In::PRIMARY).collect();
+
+    for field in primary
+        .iter()
+        .filter(|f| DESCRIPTIVE_TIFF_TAGS.contains(&f.tag))
+    {
+        let tag = Tag::Unknown(field.tag.number());
+        match &field.value {
+            Value::Ascii(v) => match v.first().and_then(|s| std::str::from_utf8(s).ok()) {
+                Some(s) if s.is_ascii() && !s.contains('\0') => directory.write_tag(tag, s)?,
+                _ => {}
+            },
+            Value::Short(v) => directory.write_tag(tag, &v[..])?,
+            Value::Long(v) => directory.write_tag(tag, &v[..])?,
+            Value::Rational(v) if v.len() == 1 => directory.write_tag(
+                tag,
+                Rational {
+                    n: v[0].num,
+                    d: v[0].denom,
+                },
+            )?,
+            _ => {}
+        }
+    }
+
+    for (context, pointer) in SUB_DIRECTORIES {
+        let fields: Vec<&Field> = primary
+            .iter()
+            .filter(|f| f.tag.context() == context && f.tag != exif::Tag::InteropIFDPointer)
+            .copied()
+            .collect();
+        if fields.is_empty() {
+            continue;
+        }
+        // Directories start on a word boundary.
+        let mut offset = directory.write_data(&[] as &[u8])?;
+        if offset % 2 == 1 {
+            offset = directory.write_data(0u8)? + 1;
+        }
+        let offset = u32::try_from(offset)?;
+        directory.write_data(&sub_directory(&fields, offset)[..])?;
+        directory.write_tag(Tag::Unknown(pointer), offset)?;
+    }
+    Ok(())
+}
+
+/// Serialize `fields` as a directory located at `offset` in the file.
+///
+/// The `tiff` crate writes the file in native byte order, so the directory does as well.
+/// Values longer than four bytes are stored right after the directory.
+fn sub_directory(fields: &[&Field], offset: u32) -> Vec<u8> {
+    let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = fields
+        .iter()
+        .filter_map(|f| {
+            let (field_type, count, bytes) = raw_value(&f.value)?;
+            Some((f.tag.number(), field_type, count, bytes))
+        })
+        .collect();
+    entries.sort_by_key(|e| e.0);
+
+    let values_offset = offset + 2 + entries.len() as u32 * 12 + 4;
+    let mut directory = (entries.len() as u16).to_ne_bytes().to_vec();
+    let mut values = Vec::new();
+    for (tag, field_type, count, bytes) in entries {
+        directory.extend_from_slice(&tag.to_ne_bytes());
+        directory.extend_from_slice(&field_type.to_ne_bytes());
+        directory.extend_from_slice(&count.to_ne_bytes());
+        if bytes.len() <= 4 {
+            let mut inline = bytes;
+            inline.resize(4, 0);
+            directory.extend_from_slice(&inline);
+        } else {
+            let value_offset = values_offset + values.len() as u32;
+            directory.extend_from_slice(&value_offset.to_ne_bytes());
+            values.extend_from_slice(&bytes);
+            if values.len() % 2 == 1 {
+                values.push(0);
+            }
+        }
+    }
+    // There is no next directory.
+    directory.extend_from_slice(&0u32.to_ne_bytes());
+    directory.extend_from_slice(&values);
+    directory
+}
+
+/// TIFF field type, count and native byte order data of `value`.
+fn raw_value(value: &Value) -> Option<(u16, u32, Vec<u8>)> {
+    let (field_type, count, bytes): (u16, usize, Vec<u8>) = match value {
+        Value::Byte(v) => (1, v.len(), v.clone()),
+        Value::Ascii(v) => {
+            let bytes: Vec<u8> = v
+                .iter()
+                .flat_map(|s| s.iter().copied().chain([0]))
+                .collect();
+            (2, bytes.len(), bytes)
+        }
+        Value::Short(v) => (3, v.len(), v.iter().flat_map(|n| n.to_ne_bytes()).collect()),
+        Value::Long(v) => (4, v.len(), v.iter().flat_map(|n| n.to_ne_bytes()).collect()),
+        Value::Rational(v) => (
+            5,
+            v.len(),
+            v.iter()
+                .flat_map(|r| [r.num, r.denom])
+                .flat_map(|n| n.to_ne_bytes())
+                .collect(),
+        ),
+        Value::SByte(v) => (6, v.len(), v.iter().map(|n| *n as u8).collect()),
+        Value::Undefined(v, _) => (7, v.len(), v.clone()),
+        Value::SShort(v) => (8, v.len(), v.iter().flat_map(|n| n.to_ne_bytes()).collect()),
+        Value::SLong(v) => (9, v.len(), v.iter().flat_map(|n| n.to_ne_bytes()).collect()),
+        Value::SRational(v) => (
+            10,
+            v.len(),
+            v.iter()
+                .flat_map(|r| [r.num, r.denom])
+                .flat_map(|n| n.to_ne_bytes())
+                .collect(),
+        ),
+        Value::Float(v) => (
+            11,
+            v.len(),
+            v.iter().flat_map(|n| n.to_ne_bytes()).collect(),
+        ),
+        Value::Double(v) => (
+            12,
+            v.len(),
+            v.iter().flat_map(|n| n.to_ne_bytes()).collect(),
+        ),
+        Value::Unknown(..) => return None,
+    };
+    Some((field_type, count as u32, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use exif::{experimental::Writer, Field, In, Reader, Tag, Value};
+    use fax::{decoder, Color};
+    use image::{DynamicImage, GrayImage, Luma, RgbImage};
+    use std::io::Cursor;
+    use tiff::{decoder::Decoder, tags::Tag as TiffTag};
+
+    use super::{EncodeOptions, ImfconvHandler, TiffHandler};
+    use crate::imfconv::handler::metadata::Metadata;
+
+    fn ascii(tag: Tag, ifd_num: In, value: &str) -> Field {
+        Field {
+            tag,
+            ifd_num,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    #[test]
+    fn exif_fields_keep_their_directories() {
+        let fields = [
+            ascii(Tag::Artist, In::PRIMARY, "Someone"),
+            ascii(Tag::DateTimeOriginal, In::PRIMARY, "2024:01:02 03:04:05"),
+            // Odd lengths pad the values stored after the directory.
+            ascii(Tag::LensModel, In::PRIMARY, "Lens"),
+            Field {
+                tag: Tag::ExposureTime,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![(1, 250).into()]),
+            },
+            ascii(Tag::GPSLatitudeRef, In::PRIMARY, "N"),
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![(35, 1).into(), (41, 1).into(), (2230, 100).into()]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut block = Cursor::new(Vec::new());
+        writer.write(&mut block, false).unwrap();
+
+        let options = EncodeOptions {
+            metadata: Metadata {
+                exif: Some(block.into_inner()),
+                ..Metadata::default()
+            },
+            ..EncodeOptions::default()
+        };
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(5, 3, [10, 200, 30].into()));
+        let encoded = TiffHandler.encode(&image, &options).unwrap();
+
+        let exif = Reader::new()
+            .read_from_container(&mut Cursor::new(&encoded.data))
+            .unwrap();
+        for field in &fields {
+            let read = exif.get_field(field.tag, In::PRIMARY).unwrap();
+            assert_eq!(
+                read.display_value().to_string(),
+                field.display_value().to_string()
+            );
+        }
+        // The samples are still where the page says they are.
+        let mut decoder = Decoder::new(Cursor::new(&encoded.data)).unwrap();
+        assert_eq!(decoder.dimensions().unwrap(), (5, 3));
+        assert!(decoder.read_image().is_ok());
+    }
+
+    #[test]
+    fn bilevel_pages_are_group_4_compressed() {
+        let pages: Vec<GrayImage> = (0..2)
+            .map(|page| {
+                GrayImage::from_fn(37, 9, |x, y| match (x / 3 + y + page) % 2 {
+                    0 => Luma([0]),
+                    _ => Luma([255]),
+                })
+            })
+            .collect();
+        let images: Vec<DynamicImage> = pages
+            .iter()
+            .cloned()
+            .map(DynamicImage::ImageLuma8)
+            .collect();
+        let options = EncodeOptions {
+            bilevel: true,
+            ..EncodeOptions::default()
         };
+        let encoded = TiffHandler.encode_pages(&images, &options).unwrap();
+
+        let mut decoder = Decoder::new(Cursor::new(&encoded.data)).unwrap();
+        for (i, page) in pages.iter().enumerate() {
+            if i > 0 {
+                decoder.next_image().unwrap();
+            }
+            assert_eq!(decoder.get_tag_u32(TiffTag::Compression).unwrap(), 4);
+            assert_eq!(decoder.get_tag_u32(TiffTag::BitsPerSample).unwrap(), 1);
+            let offset = decoder.get_tag_u32(TiffTag::StripOffsets).unwrap() as usize;
+            let length = decoder.get_tag_u32(TiffTag::StripByteCounts).unwrap() as usize;
+            let strip = &encoded.data[offset..offset + length];
 
-        let dest_filepath = dest_filepath.with_extension("tiff");
-        let decoded_image = image::DynamicImage::from(raw_image);
-        match decoded_image.save(&dest_filepath) {
-            Ok(_) => return Ok(()),
-            Err(e) => return Err(Box::new(e)),
+            let mut rows = Vec::new();
+            decoder::decode_g4(
+                strip.iter().copied(),
+                page.width(),
+                Some(page.height()),
+                |line| {
+                    rows.extend(decoder::pels(line, page.width()).map(|c| match c {
+                        Color::Black => 0,
+                        Color::White => 255,
+                    }));
+                },
+            )
+            .unwrap();
+            assert_eq!(rows, page.as_raw()[..]);
         }
+        assert!(!decoder.more_images());
     }
 }
@@ -0,0 +1,268 @@
+use super::{fit_quality, to_rgb8_or_rgba8, EncodeOptions, Encoded, ImfconvHandler};
+use crate::imfconv::handler::{animation::Animation, metadata::Metadata};
+use image::{
+    codecs::webp::{WebPEncoder, WebPQuality},
+    DynamicImage,
+};
+use libwebp_sys::{
+    WebPAnimEncoder, WebPAnimEncoderAdd, WebPAnimEncoderAssemble, WebPAnimEncoderDelete,
+    WebPAnimEncoderGetError, WebPAnimEncoderNewInternal, WebPAnimEncoderOptions,
+    WebPAnimEncoderOptionsInitInternal, WebPConfig, WebPData, WebPDataClear, WebPGetMuxABIVersion,
+    WebPMux, WebPMuxAssemble, WebPMuxCreateInternal, WebPMuxDelete, WebPMuxError, WebPMuxSetChunk,
+    WebPPicture, WebPPictureFree, WebPPictureImportRGB, WebPPictureImportRGBA,
+};
+use std::{error::Error, ffi::CStr, mem::MaybeUninit, ptr, slice};
+
+pub struct WebpHandler;
+impl ImfconvHandler for WebpHandler {
+    fn supports_alpha(&self) -> bool {
+        true
+    }
+
+    fn extension(&self) -> &'static str {
+        "webp"
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let raw_image = to_rgb8_or_rgba8(image);
+        let encode = |quality: WebPQuality| {
+            embed_metadata(&encode_still(&raw_image, quality)?, options.metadata())
+        };
+        let data = match (options.webp_lossless, options.target_size_bytes) {
+            (true, _) => encode(WebPQuality::lossless())?,
+            (false, Some(target_size)) => {
+                fit_quality(target_size, options.webp_quality, |quality| {
+                    encode(WebPQuality::lossy(quality))
+                })?
+            }
+            (false, None) => encode(WebPQuality::lossy(options.webp_quality))?,
+        };
+        Ok(Encoded {
+            data,
+            extension: "webp",
+            mime_type: "image/webp",
+        })
+    }
+
+    fn encode_animation(
+        &self,
+        animation: &Animation,
+        options: &EncodeOptions,
+    ) -> Result<Encoded, Box<dyn Error>> {
+        let frames: Vec<(DynamicImage, u32)> = animation
+            .frames
+            .iter()
+            .map(|f| (to_rgb8_or_rgba8(&f.image), f.delay_ms))
+            .collect();
+
+        let mut config = match WebPConfig::new() {
+            Ok(c) => c,
+            Err(_) => return Err("failed to initialize the WebP encoder".into()),
+        };
+        let encode = |config: &WebPConfig| {
+            let data = encode_animated(&frames, animation.loop_count, config)?;
+            embed_metadata(&data, options.metadata())
+        };
+        let data = match (options.webp_lossless, options.target_size_bytes) {
+            (true, _) => {
+                config.lossless = 1;
+                encode(&config)?
+            }
+            (false, Some(target_size)) => {
+                fit_quality(target_size, options.webp_quality, |quality| {
+                    config.quality = quality as f32;
+                    encode(&config)
+                })?
+            }
+            (false, None) => {
+                config.quality = options.webp_quality.min(100) as f32;
+                encode(&config)?
+            }
+        };
+
+        Ok(Encoded {
+            data,
+            extension: "webp",
+            mime_type: "image/webp",
+        })
+    }
+}
+
+/// Encode an 8 bit RGB or RGBA image as a still WebP file.
+fn encode_still(raw_image: &DynamicImage, quality: WebPQuality) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    let encoder = WebPEncoder::new_with_quality(&mut data, quality);
+    match encoder.encode(
+        raw_image.as_bytes(),
+        raw_image.width(),
+        raw_image.height(),
+        raw_image.color(),
+    ) {
+        Ok(_) => Ok(data),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Add the metadata chunks to the WebP file in `data`.
+///
+/// The file is returned unchanged if there is no metadata to add.
+fn embed_metadata(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>, Box<dyn Error>> {
+    let chunks: Vec<(&[u8; 4], &Vec<u8>)> = [
+        (b"ICCP", &metadata.icc_profile),
+        (b"EXIF", &metadata.exif),
+        (b"XMP ", &metadata.xmp),
+    ]
+    .into_iter()
+    .filter_map(|(fourcc, chunk)| chunk.as_ref().map(|c| (fourcc, c)))
+    .collect();
+    if chunks.is_empty() {
+        return Ok(data.to_vec());
+    }
+
+    // The `image` crate cannot write metadata to WebP, so the chunks are added with libwebp.
+    unsafe {
+        let webp_data = WebPData {
+            bytes: data.as_ptr(),
+            size: data.len(),
+        };
+        let mux = WebPMuxCreateInternal(&webp_data, 0, WebPGetMuxABIVersion());
+        if mux.is_null() {
+            return Err("failed to read the encoded WebP file".into());
+        }
+        let data = assemble_with_chunks(mux, &chunks);
+        WebPMuxDelete(mux);
+        data
+    }
+}
+
+/// Set every chunk in `mux` and assemble the WebP file.
+///
+/// # Safety
+///
+/// `mux` must be a valid WebP muxer.
+unsafe fn assemble_with_chunks(
+    mux: *mut WebPMux,
+    chunks: &[(&[u8; 4], &Vec<u8>)],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    for (fourcc, chunk) in chunks {
+        let chunk_data = WebPData {
+            bytes: chunk.as_ptr(),
+            size: chunk.len(),
+        };
+        if WebPMuxSetChunk(mux, fourcc.as_ptr() as *const _, &chunk_data, 1)
+            != WebPMuxError::WEBP_MUX_OK
+        {
+            return Err("failed to add the metadata to the WebP file".into());
+        }
+    }
+
+    let mut webp_data = WebPData::default();
+    if WebPMuxAssemble(mux, &mut webp_data) != WebPMuxError::WEBP_MUX_OK {
+        return Err("failed to add the metadata to the WebP file".into());
+    }
+    let data = slice::from_raw_parts(webp_data.bytes, webp_data.size).to_vec();
+    WebPDataClear(&mut webp_data);
+    Ok(data)
+}
+
+/// Encode the 8 bit RGB or RGBA frames and their delays as an animated WebP file.
+fn encode_animated(
+    frames: &[(DynamicImage, u32)],
+    loop_count: u16,
+    config: &WebPConfig,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (width, height) = match frames.first() {
+        Some((f, _)) => (f.width(), f.height()),
+        None => return Err("the animation has no frames".into()),
+    };
+
+    // The `image` crate has no animated WebP encoder, so libwebp is used directly.
+    unsafe {
+        let mut anim_options = MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(anim_options.as_mut_ptr(), WebPGetMuxABIVersion())
+            == 0
+        {
+            return Err("failed to initialize the WebP animation encoder".into());
+        }
+        let mut anim_options = anim_options.assume_init();
+        anim_options.anim_params.loop_count = loop_count.into();
+        let encoder = WebPAnimEncoderNewInternal(
+            width as i32,
+            height as i32,
+            &anim_options,
+            WebPGetMuxABIVersion(),
+        );
+        if encoder.is_null() {
+            return Err("failed to create the WebP animation encoder".into());
+        }
+        let data = encode_frames(encoder, frames, config);
+        WebPAnimEncoderDelete(encoder);
+        data
+    }
+}
+
+/// Add every frame to `encoder` and assemble the animated WebP file.
+///
+/// # Safety
+///
+/// `encoder` must be a valid animation encoder created for frames of this size.
+unsafe fn encode_frames(
+    encoder: *mut WebPAnimEncoder,
+    frames: &[(DynamicImage, u32)],
+    config: &WebPConfig,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut timestamp: i32 = 0;
+    for (image, delay_ms) in frames {
+        let mut picture = match WebPPicture::new() {
+            Ok(p) => p,
+            Err(_) => return Err("failed to initialize a WebP picture".into()),
+        };
+        picture.use_argb = 1;
+        picture.width = image.width() as i32;
+        picture.height = image.height() as i32;
+        let samples = image.as_bytes().as_ptr();
+        let imported = match image.color().has_alpha() {
+            true => WebPPictureImportRGBA(&mut picture, samples, image.width() as i32 * 4),
+            false => WebPPictureImportRGB(&mut picture, samples, image.width() as i32 * 3),
+        };
+        let added =
+            imported != 0 && WebPAnimEncoderAdd(encoder, &mut picture, timestamp, config) != 0;
+        WebPPictureFree(&mut picture);
+        if !added {
+            return Err(encoder_error(encoder));
+        }
+        timestamp = timestamp.saturating_add(*delay_ms as i32);
+    }
+    // The final call without a picture sets the duration of the last frame.
+    if WebPAnimEncoderAdd(encoder, ptr::null_mut(), timestamp, ptr::null()) == 0 {
+        return Err(encoder_error(encoder));
+    }
+
+    let mut webp_data = WebPData::default();
+    if WebPAnimEncoderAssemble(encoder, &mut webp_data) == 0 {
+        return Err(encoder_error(encoder));
+    }
+    let data = slice::from_raw_parts(webp_data.bytes, webp_data.size).to_vec();
+    WebPDataClear(&mut webp_data);
+    Ok(data)
+}
+
+/// The last error reported by `encoder`.
+///
+/// # Safety
+///
+/// `encoder` must be a valid animation encoder.
+unsafe fn encoder_error(encoder: *mut WebPAnimEncoder) -> Box<dyn Error> {
+    let message = WebPAnimEncoderGetError(encoder);
+    if message.is_null() {
+        return "failed to encode the WebP animation".into();
+    }
+    CStr::from_ptr(message)
+        .to_string_lossy()
+        .into_owned()
+        .into()
+}
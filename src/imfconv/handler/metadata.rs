@@ -0,0 +1,89 @@
+use exif::Tag;
+
+/// Fields of the primary TIFF directory that describe the image rather than its data layout,
+/// the only ones carried over between EXIF blocks and TIFF files.
+pub const DESCRIPTIVE_TIFF_TAGS: [Tag; 11] = [
+    Tag::ImageDescription,
+    Tag::Make,
+    Tag::Model,
+    Tag::Orientation,
+    Tag::XResolution,
+    Tag::YResolution,
+    Tag::ResolutionUnit,
+    Tag::Software,
+    Tag::DateTime,
+    Tag::Artist,
+    Tag::Copyright,
+];
+
+/// Namespace that starts the APP1 segment holding an XMP packet in JPEG files.
+pub const XMP_JPEG_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Signature that starts the APP13 segment holding Photoshop resources in JPEG files.
+pub const PHOTOSHOP_JPEG_SIGNATURE: &[u8] = b"Photoshop 3.0\0";
+/// Identifier of the Photoshop resource holding IPTC-IIM records.
+pub const IPTC_RESOURCE_ID: u16 = 0x0404;
+
+/// Metadata blocks embedded in the output next to the pixel data.
+///
+/// Handlers write the blocks their format can hold and skip the others.
+#[derive(Default, Clone)]
+pub struct Metadata {
+    /// EXIF data, starting with the TIFF header.
+    pub exif: Option<Vec<u8>>,
+    /// ICC color profile.
+    pub icc_profile: Option<Vec<u8>>,
+    /// XMP packet.
+    pub xmp: Option<Vec<u8>>,
+    /// IPTC-IIM records.
+    pub iptc: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// Metadata without any block.
+    pub const EMPTY: Metadata = Metadata {
+        exif: None,
+        icc_profile: None,
+        xmp: None,
+        iptc: None,
+    };
+
+    /// Overwrite the orientation field of the EXIF block, if it has one.
+    pub fn set_exif_orientation(&mut self, orientation: u16) {
+        let exif = match &mut self.exif {
+            Some(e) => e,
+            None => return,
+        };
+        if let Some((offset, little_endian)) = orientation_offset(exif) {
+            let value = match little_endian {
+                true => orientation.to_le_bytes(),
+                false => orientation.to_be_bytes(),
+            };
+            exif[offset..offset + 2].copy_from_slice(&value);
+        }
+    }
+}
+
+/// Offset of the orientation value in the primary directory of the EXIF block `exif`,
+/// and whether the block is little endian.
+fn orientation_offset(exif: &[u8]) -> Option<(usize, bool)> {
+    let little_endian = exif.starts_with(b"II");
+    let read_u16 = |b: &[u8]| match little_endian {
+        true => u16::from_le_bytes([b[0], b[1]]),
+        false => u16::from_be_bytes([b[0], b[1]]),
+    };
+    let ifd = exif.get(4..8)?;
+    let ifd = match little_endian {
+        true => u32::from_le_bytes([ifd[0], ifd[1], ifd[2], ifd[3]]),
+        false => u32::from_be_bytes([ifd[0], ifd[1], ifd[2], ifd[3]]),
+    } as usize;
+    let count = read_u16(exif.get(ifd..ifd + 2)?) as usize;
+    for i in 0..count {
+        let entry = exif.get(ifd + 2 + i * 12..ifd + 14 + i * 12)?;
+        // A single SHORT is stored in the first bytes of the value field.
+        if read_u16(&entry[..2]) == Tag::Orientation.number() && read_u16(&entry[2..4]) == 3 {
+            return Some((ifd + 2 + i * 12 + 8, little_endian));
+        }
+    }
+    None
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+
+use image::{imageops, DynamicImage, ImageBuffer, Rgba};
+
+use super::{format::to_rgba16, is_16bit, transform::Transform};
+
+/// Grid of a contact sheet, every image is scaled down into a cell of its own.
+pub struct Montage {
+    pub columns: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+    /// Pixels between neighbouring cells.
+    pub gap: u32,
+    /// RGBA color of the gaps and of the cells around the images.
+    pub background: [u8; 4],
+}
+
+impl Montage {
+    /// `images` arranged row by row in the grid on the background color.
+    ///
+    /// Images larger than a cell are scaled down to fit, keeping their aspect ratio,
+    /// and every image is centered in its cell. The sheet holds 16 bit samples if a source
+    /// does and an alpha channel if a source or the background has one.
+    pub fn compose(&self, images: &[DynamicImage]) -> Result<DynamicImage, Box<dyn Error>> {
+        let count = images.len() as u32;
+        let columns = self.columns.min(count).max(1);
+        let rows = count.div_ceil(columns).max(1);
+        let extent = |cells: u32, cell: u32| {
+            cells
+                .checked_mul(cell)?
+                .checked_add((cells - 1).checked_mul(self.gap)?)
+        };
+        let (width, height) = match (
+            extent(columns, self.cell_width),
+            extent(rows, self.cell_height),
+        ) {
+            (Some(w), Some(h)) => (w, h),
+            _ => return Err("the montage is too large".into()),
+        };
+
+        let background = Rgba(self.background.map(|c| c as u16 * 257));
+        let mut sheet = ImageBuffer::from_pixel(width, height, background);
+        let pad = Transform::Pad {
+            width: self.cell_width,
+            height: self.cell_height,
+            color: self.background,
+        };
+        for (i, image) in images.iter().enumerate() {
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            let x = column * (self.cell_width + self.gap);
+            let y = row * (self.cell_height + self.gap);
+            let cell = to_rgba16(&pad.apply(image)?);
+            imageops::replace(&mut sheet, &cell, x as i64, y as i64);
+        }
+
+        let sheet = DynamicImage::ImageRgba16(sheet);
+        let alpha = self.background[3] < 255 || images.iter().any(|i| i.color().has_alpha());
+        Ok(match (images.iter().any(is_16bit), alpha) {
+            (true, true) => sheet,
+            (true, false) => DynamicImage::ImageRgb16(sheet.to_rgb16()),
+            (false, true) => DynamicImage::ImageRgba8(sheet.to_rgba8()),
+            (false, false) => DynamicImage::ImageRgb8(sheet.to_rgb8()),
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use color_quant::NeuQuant;
+use image::RgbaImage;
+
+use crate::imfconv::Dither;
+
+/// Sampling factor handed to NeuQuant. 1 is the slowest and most accurate, 30 the fastest.
+const NEUQUANT_SAMPLE_FACTOR: i32 = 10;
+
+/// 8x8 Bayer matrix with thresholds from 0 to 63.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Image data reduced to a color palette.
+pub struct Quantized {
+    /// Palette entries as packed RGB triples.
+    pub palette: Vec<u8>,
+    /// Alpha of every palette entry, 255 for opaque colors.
+    pub alpha: Vec<u8>,
+    /// One palette index per pixel.
+    pub indices: Vec<u8>,
+}
+
+impl Quantized {
+    /// Whether a palette entry is not opaque.
+    pub fn is_translucent(&self) -> bool {
+        self.alpha.iter().any(|a| *a < 255)
+    }
+}
+
+/// Reduce an RGBA image to at most `max_colors` colors, from 2 to 256.
+///
+/// Images that already fit into the palette keep their exact colors,
+/// otherwise the palette is computed with NeuQuant and the pixels are mapped with `dither`.
+/// Fully transparent pixels share one entry whatever their color.
+pub fn quantize(image: &RgbaImage, max_colors: usize, dither: Dither) -> Quantized {
+    match exact_palette(image, max_colors) {
+        Some(q) => q,
+        None => neuquant_palette(image, max_colors, dither),
+    }
+}
+
+/// The RGBA samples of `pixel`, black if it is fully transparent.
+fn visible(pixel: &[u8]) -> [u8; 4] {
+    match pixel[3] {
+        0 => [0; 4],
+        a => [pixel[0], pixel[1], pixel[2], a],
+    }
+}
+
+fn exact_palette(raw_image: &[u8], max_colors: usize) -> Option<Quantized> {
+    let mut palette = Vec::new();
+    let mut alpha = Vec::new();
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(raw_image.len() / 4);
+    for pixel in raw_image.chunks_exact(4) {
+        let color = visible(pixel);
+        let index = match lookup.get(&color) {
+            Some(i) => *i,
+            None => {
+                if lookup.len() == max_colors {
+                    return None;
+                }
+                let i = lookup.len() as u8;
+                lookup.insert(color, i);
+                palette.extend_from_slice(&color[..3]);
+                alpha.push(color[3]);
+                i
+            }
+        };
+        indices.push(index);
+    }
+    Some(Quantized {
+        palette,
+        alpha,
+        indices,
+    })
+}
+
+fn neuquant_palette(image: &RgbaImage, max_colors: usize, dither: Dither) -> Quantized {
+    let rgba: Vec<u8> = image.chunks_exact(4).flat_map(visible).collect();
+    let quant = NeuQuant::new(NEUQUANT_SAMPLE_FACTOR, max_colors, &rgba);
+    let colors = quant.color_map_rgba();
+    let palette: Vec<u8> = colors
+        .chunks_exact(4)
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect();
+    let alpha: Vec<u8> = colors.chunks_exact(4).map(|c| c[3]).collect();
+    let index_of =
+        |color: [f32; 4]| quant.index_of(&color.map(|c| c.round().clamp(0.0, 255.0) as u8)) as u8;
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let pixel = |x: usize, y: usize| visible(&image.get_pixel(x as u32, y as u32).0).map(f32::from);
+    let indices = match dither {
+        Dither::NONE => rgba
+            .chunks_exact(4)
+            .map(|p| quant.index_of(p) as u8)
+            .collect(),
+        Dither::ORDERED => {
+            // Offset every pixel by its threshold, scaled to the typical distance of palette colors.
+            let spread = 255.0 / (max_colors as f32).cbrt();
+            (0..width * height)
+                .map(|i| {
+                    let (x, y) = (i % width, i / width);
+                    let threshold = (f32::from(BAYER[y % 8][x % 8]) + 0.5) / 64.0 - 0.5;
+                    let mut color = pixel(x, y);
+                    // Alpha is matched as it is, dithering it would speckle opaque areas.
+                    for c in &mut color[..3] {
+                        *c += threshold * spread;
+                    }
+                    index_of(color)
+                })
+                .collect()
+        }
+        Dither::FLOYDSTEINBERG => {
+            let mut indices = Vec::with_capacity(width * height);
+            let mut errors = vec![[0f32; 4]; width];
+            let mut next_errors = vec![[0f32; 4]; width];
+            for y in 0..height {
+                for x in 0..width {
+                    let mut color = pixel(x, y);
+                    for (c, e) in color.iter_mut().zip(errors[x]) {
+                        *c = (*c + e).clamp(0.0, 255.0);
+                    }
+                    let index = index_of(color);
+                    indices.push(index);
+
+                    // Spread the difference to the chosen color over the unvisited neighbours.
+                    let chosen = &colors[usize::from(index) * 4..usize::from(index) * 4 + 4];
+                    for c in 0..3 {
+                        let error = color[c] - f32::from(chosen[c]);
+                        if x + 1 < width {
+                            errors[x + 1][c] += error * 7.0 / 16.0;
+                            next_errors[x + 1][c] += error / 16.0;
+                        }
+                        if x > 0 {
+                            next_errors[x - 1][c] += error * 3.0 / 16.0;
+                        }
+                        next_errors[x][c] += error * 5.0 / 16.0;
+                    }
+                }
+                errors = std::mem::replace(&mut next_errors, vec![[0f32; 4]; width]);
+            }
+            indices
+        }
+    };
+    Quantized {
+        palette,
+        alpha,
+        indices,
+    }
+}
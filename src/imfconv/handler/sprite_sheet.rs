@@ -0,0 +1,112 @@
+use image::{imageops, DynamicImage, ImageBuffer, Rgba};
+
+use super::{format::to_rgba16, is_16bit};
+
+/// Images packed into one transparent sheet, `padding` pixels apart.
+pub struct SpriteSheet {
+    pub padding: u32,
+}
+
+impl SpriteSheet {
+    /// The sheet holding `images` and the top left corner of every image in it.
+    ///
+    /// The images are packed into rows from the tallest to the shortest, the rows are about
+    /// as wide as the sheet is high. The sheet holds 16 bit samples if a source does.
+    pub fn pack(&self, images: &[DynamicImage]) -> (DynamicImage, Vec<(u32, u32)>) {
+        let padding = self.padding as u64;
+        let area: u64 = images
+            .iter()
+            .map(|i| (i.width() as u64 + padding) * (i.height() as u64 + padding))
+            .sum();
+        let widest = images.iter().map(|i| i.width()).max().unwrap_or(1);
+        let row_width = ((area as f64).sqrt().ceil() as u32).max(widest);
+
+        let mut order: Vec<usize> = (0..images.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse(images[*i].height()));
+        let mut positions = vec![(0, 0); images.len()];
+        let (mut x, mut y, mut row_height) = (0, 0, 0);
+        let (mut width, mut height) = (1, 1);
+        for i in order {
+            let image = &images[i];
+            if x > 0 && x + image.width() > row_width {
+                x = 0;
+                y += row_height + self.padding;
+                row_height = 0;
+            }
+            positions[i] = (x, y);
+            width = width.max(x + image.width());
+            height = height.max(y + image.height());
+            x += image.width() + self.padding;
+            row_height = row_height.max(image.height());
+        }
+
+        let mut sheet = ImageBuffer::from_pixel(width, height, Rgba([0u16; 4]));
+        for (image, (x, y)) in images.iter().zip(&positions) {
+            imageops::replace(&mut sheet, &to_rgba16(image), *x as i64, *y as i64);
+        }
+        let sheet = DynamicImage::ImageRgba16(sheet);
+        let sheet = match images.iter().any(is_16bit) {
+            true => sheet,
+            false => DynamicImage::ImageRgba8(sheet.to_rgba8()),
+        };
+        (sheet, positions)
+    }
+}
+
+/// The JSON map of the sprites in the sheet `image_name`, in the hash layout of TexturePacker
+/// that game and web frameworks read.
+///
+/// Every sprite is keyed by its entry in `names` and has the size of its entry in `images`.
+pub fn sprite_map(
+    image_name: &str,
+    sheet: &DynamicImage,
+    names: &[String],
+    images: &[DynamicImage],
+    positions: &[(u32, u32)],
+) -> String {
+    let frames: Vec<String> = names
+        .iter()
+        .zip(images)
+        .zip(positions)
+        .map(|((name, image), (x, y))| {
+            let (w, h) = (image.width(), image.height());
+            format!(
+                "    \"{}\": {{\"frame\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {}}}, \
+                 \"rotated\": false, \"trimmed\": false, \
+                 \"spriteSourceSize\": {{\"x\": 0, \"y\": 0, \"w\": {}, \"h\": {}}}, \
+                 \"sourceSize\": {{\"w\": {}, \"h\": {}}}}}",
+                escape(name),
+                x,
+                y,
+                w,
+                h,
+                w,
+                h,
+                w,
+                h
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"frames\": {{\n{}\n  }},\n  \"meta\": {{\"image\": \"{}\", \
+         \"size\": {{\"w\": {}, \"h\": {}}}, \"scale\": \"1\"}}\n}}\n",
+        frames.join(",\n"),
+        escape(image_name),
+        sheet.width(),
+        sheet.height()
+    )
+}
+
+/// `value` as the contents of a JSON string.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,21 @@
+//! The sRGB transfer function, used to move between linear float data and 8 bit images.
+
+/// Encode a linear light value (0.0 to 1.0) with the sRGB curve.
+pub fn encode(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode an sRGB encoded value (0.0 to 1.0) to linear light.
+pub fn decode(encoded: f32) -> f32 {
+    let encoded = encoded.clamp(0.0, 1.0);
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
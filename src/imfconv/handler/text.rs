@@ -0,0 +1,69 @@
+use std::error::Error;
+
+use image::{DynamicImage, RgbaImage};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+/// Rasterize the single line `text` in the font family `font` at `size` pixels with the
+/// RGBA color `color` on a transparent background.
+///
+/// `font` may list fallback families separated by commas as in CSS, e.g. `"Arial, sans-serif"`.
+/// Fonts are looked up from the fonts installed on the system.
+/// The image is cropped to the bounds of the glyphs.
+pub fn render_text(
+    text: &str,
+    font: &str,
+    size: f32,
+    color: [u8; 4],
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let [r, g, b, a] = color;
+    let svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1\" height=\"1\">\
+         <text x=\"0\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" \
+         fill=\"rgb({},{},{})\" fill-opacity=\"{}\" xml:space=\"preserve\">{}</text></svg>",
+        size,
+        escape(font),
+        size,
+        r,
+        g,
+        b,
+        a as f32 / 255.0,
+        escape(text)
+    );
+    let mut options = Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = match Tree::from_data(svg.as_bytes(), &options) {
+        Ok(t) => t,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let bounds = tree.root().abs_bounding_box();
+    let (left, top) = (bounds.left().floor(), bounds.top().floor());
+    let width = (bounds.right().ceil() - left) as u32;
+    let height = (bounds.bottom().ceil() - top) as u32;
+    let mut pixmap = match Pixmap::new(width, height) {
+        Some(p) => p,
+        None => return Err(format!("no font could render the text \"{}\"", text).into()),
+    };
+    resvg::render(
+        &tree,
+        Transform::from_translate(-left, -top),
+        &mut pixmap.as_mut(),
+    );
+
+    match RgbaImage::from_vec(width, height, pixmap.take_demultiplied()) {
+        Some(i) => Ok(DynamicImage::ImageRgba8(i)),
+        None => Err("the text could not be rasterized".into()),
+    }
+}
+
+/// `value` with the characters that are special in XML replaced by entities.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
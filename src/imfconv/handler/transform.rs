@@ -0,0 +1,896 @@
+use std::{
+    error::Error,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use image::{imageops::FilterType, DynamicImage, Rgba, Rgba32FImage};
+
+use super::{format::to_srgb, is_16bit, is_float, srgb, text::render_text};
+use crate::imfconv::{reader::read_image, Anchor, Denoise, ResizeFilter, ResizeMode};
+
+/// Largest difference of a sample from the corner color that still counts as border.
+const TRIM_TOLERANCE: f32 = 0.02;
+/// Standard deviation of the color differences the bilateral filter smooths over.
+const BILATERAL_RANGE_SIGMA: f32 = 0.1;
+
+type MapFn = dyn Fn(&mut [u8; 4]) + Send + Sync;
+
+/// Operations applied to every image in order, before the color profile.
+///
+/// The builder methods of `Imfconv` add them one by one. A list returned by
+/// `Imfconv::transforms` can be replayed on another conversion with `Imfconv::push_transform`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transform {
+    /// Scale to `width` x `height` pixels as framed by `mode`.
+    Resize {
+        width: u32,
+        height: u32,
+        mode: ResizeMode,
+        filter: ResizeFilter,
+    },
+    /// Scale down to fit inside `max_width` x `max_height` pixels, keeping the aspect ratio.
+    Thumbnail { max_width: u32, max_height: u32 },
+    /// Cut out the `width` x `height` region whose top left corner is at `x`, `y`.
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Rotate clockwise by `degrees`, filling the uncovered corners with the RGBA `fill`.
+    Rotate { degrees: f64, fill: [u8; 4] },
+    /// Mirror left to right.
+    FlipHorizontal,
+    /// Mirror top to bottom.
+    FlipVertical,
+    /// Composite the image at `path` at `anchor`, `margin` pixels away from the edges,
+    /// with `opacity`. The image is decoded once into `stamp`.
+    Watermark {
+        path: PathBuf,
+        anchor: Anchor,
+        margin: u32,
+        opacity: f32,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        stamp: Stamp,
+    },
+    /// Stamp `text` in the font family `font` at `size` pixels and the RGBA `color`
+    /// at `anchor`, `margin` pixels away from the edges. The text is rendered once into `stamp`.
+    Text {
+        text: String,
+        font: String,
+        size: f32,
+        color: [u8; 4],
+        anchor: Anchor,
+        margin: u32,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        stamp: Stamp,
+    },
+    /// Add `brightness` in 8 bit steps, then scale the distance from mid gray by `contrast`.
+    Adjust { brightness: i32, contrast: f32 },
+    /// Raise every color sample from `0.0` to `1.0` to `1 / gamma`.
+    Gamma { gamma: f32 },
+    /// Gaussian blur with the standard deviation `sigma` in pixels.
+    Blur { sigma: f32 },
+    /// Unsharp mask adding `amount` times the detail finer than a blur with `sigma`,
+    /// skipping the pixels whose detail does not exceed `threshold`.
+    Sharpen {
+        sigma: f32,
+        amount: f32,
+        threshold: f32,
+    },
+    /// Reduce noise with `filter` over the neighbours up to `radius` pixels away.
+    Denoise { filter: Denoise, radius: u32 },
+    /// Darken towards the corners by up to `strength`, starting at `radius` of the way
+    /// from the center to the corners.
+    Vignette { strength: f32, radius: f32 },
+    /// Remove the borders of the color of the top left pixel, or of transparent pixels.
+    Trim,
+    /// Rotate the HSL hue by `hue` degrees and scale the HSL saturation by `saturation`.
+    HueSaturation { hue: f32, saturation: f32 },
+    /// Add `top`, `right`, `bottom` and `left` pixels of the RGBA `color` around the image.
+    ExtendCanvas {
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        color: [u8; 4],
+    },
+    /// Center on a `width` x `height` canvas of the RGBA `color`, scaling down to fit.
+    Pad {
+        width: u32,
+        height: u32,
+        color: [u8; 4],
+    },
+    /// Call a function on the 8 bit sRGB encoded RGBA samples of every pixel.
+    /// Lists holding it cannot be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    MapPixels(PixelMap),
+}
+
+/// Function changing the RGBA samples of a pixel in place, see `Imfconv::map_pixels`.
+///
+/// Maps are equal only if they share the same function.
+#[derive(Clone)]
+pub struct PixelMap(Arc<MapFn>);
+
+impl PixelMap {
+    pub fn new<F: Fn(&mut [u8; 4]) + Send + Sync + 'static>(map: F) -> Self {
+        PixelMap(Arc::new(map))
+    }
+}
+
+impl fmt::Debug for PixelMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PixelMap")
+    }
+}
+
+impl PartialEq for PixelMap {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Samples of a watermark or a rendered text, prepared on first use and shared by the
+/// copies of the operation, e.g. the conversions replaying the same list.
+///
+/// Stamps are always equal, their samples follow from the other fields of the operation.
+#[derive(Clone, Default)]
+pub struct Stamp(Arc<OnceLock<Rgba32FImage>>);
+
+impl Stamp {
+    /// The samples of the image `prepare` returns, calling it only the first time.
+    fn get_or_prepare<F>(&self, prepare: F) -> Result<&Rgba32FImage, Box<dyn Error>>
+    where
+        F: FnOnce() -> Result<DynamicImage, Box<dyn Error>>,
+    {
+        if let Some(samples) = self.0.get() {
+            return Ok(samples);
+        }
+        let samples = watermark_samples(&prepare()?);
+        Ok(self.0.get_or_init(|| samples))
+    }
+}
+
+impl fmt::Debug for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Stamp")
+    }
+}
+
+impl PartialEq for Stamp {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Transform {
+    /// The result of the operation on `image`.
+    pub fn apply(&self, image: &DynamicImage) -> Result<DynamicImage, Box<dyn Error>> {
+        match self {
+            Transform::Resize {
+                width,
+                height,
+                mode,
+                filter,
+            } => {
+                let (width, height, filter) = (*width, *height, filter_type(*filter));
+                Ok(match mode {
+                    ResizeMode::FIT => image.resize(width, height, filter),
+                    ResizeMode::FILL => image.resize_exact(width, height, filter),
+                    ResizeMode::COVER => image.resize_to_fill(width, height, filter),
+                })
+            }
+            Transform::Thumbnail {
+                max_width,
+                max_height,
+            } => Ok(thumbnail(image, *max_width, *max_height)),
+            Transform::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let fits_x = x.checked_add(*width).is_some_and(|r| r <= image.width());
+                let fits_y = y.checked_add(*height).is_some_and(|b| b <= image.height());
+                if !fits_x || !fits_y {
+                    return Err(format!(
+                        "the crop region of {}x{} pixels at {},{} exceeds the {}x{} image",
+                        width,
+                        height,
+                        x,
+                        y,
+                        image.width(),
+                        image.height()
+                    )
+                    .into());
+                }
+                Ok(image.crop_imm(*x, *y, *width, *height))
+            }
+            Transform::Rotate { degrees, fill } => Ok(rotate(image, *degrees, *fill)),
+            Transform::FlipHorizontal => Ok(image.fliph()),
+            Transform::FlipVertical => Ok(image.flipv()),
+            Transform::Watermark {
+                path,
+                anchor,
+                margin,
+                opacity,
+                stamp,
+            } => {
+                let watermark = stamp.get_or_prepare(|| read_image(path))?;
+                Ok(overlay(image, watermark, *anchor, *margin, *opacity))
+            }
+            Transform::Text {
+                text,
+                font,
+                size,
+                color,
+                anchor,
+                margin,
+                stamp,
+            } => {
+                let watermark = stamp.get_or_prepare(|| render_text(text, font, *size, *color))?;
+                Ok(overlay(image, watermark, *anchor, *margin, 1.0))
+            }
+            Transform::Adjust {
+                brightness,
+                contrast,
+            } => Ok(adjust(image, *brightness, *contrast)),
+            Transform::Gamma { gamma } => Ok(correct_gamma(image, *gamma)),
+            Transform::Blur { sigma } => Ok(image.blur(*sigma)),
+            Transform::Sharpen {
+                sigma,
+                amount,
+                threshold,
+            } => Ok(sharpen(image, *sigma, *amount, *threshold)),
+            Transform::Denoise { filter, radius } => Ok(denoise(image, *filter, *radius)),
+            Transform::Vignette { strength, radius } => Ok(vignette(image, *strength, *radius)),
+            Transform::Trim => Ok(trim(image)),
+            Transform::HueSaturation { hue, saturation } => {
+                Ok(hue_saturation(image, *hue, *saturation))
+            }
+            Transform::Pad {
+                width,
+                height,
+                color,
+            } => Ok(pad(image, *width, *height, *color)),
+            Transform::ExtendCanvas {
+                top,
+                right,
+                bottom,
+                left,
+                color,
+            } => {
+                let width = left
+                    .checked_add(image.width())
+                    .and_then(|w| w.checked_add(*right));
+                let height = top
+                    .checked_add(image.height())
+                    .and_then(|h| h.checked_add(*bottom));
+                match (width, height) {
+                    (Some(w), Some(h)) => Ok(place(image, w, h, *left, *top, *color)),
+                    _ => Err("the extended canvas is too large".into()),
+                }
+            }
+            Transform::MapPixels(map) => Ok(map_pixels(image, map)),
+        }
+    }
+
+    /// Check the parameters of the operation, which the builder methods of `Imfconv` do
+    /// for every operation they add.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let positive = |v: f32| !v.is_nan() && v > 0.0;
+        let non_negative = |v: f32| !v.is_nan() && v >= 0.0;
+        let error = match self {
+            Transform::Resize { width, height, .. } if *width == 0 || *height == 0 => {
+                "the size of the resized image must not be zero"
+            }
+            Transform::Thumbnail {
+                max_width,
+                max_height,
+            } if *max_width == 0 || *max_height == 0 => {
+                "the size of the thumbnail must not be zero"
+            }
+            Transform::Crop { width, height, .. } if *width == 0 || *height == 0 => {
+                "the size of the crop region must not be zero"
+            }
+            Transform::Rotate { degrees, .. } if !degrees.is_finite() => {
+                "the rotation angle must be a finite number"
+            }
+            Transform::Watermark { opacity, .. } if !(0.0..=1.0).contains(opacity) => {
+                "the watermark opacity must be from 0.0 to 1.0"
+            }
+            Transform::Text { size, .. } if !positive(*size) => "the font size must be positive",
+            Transform::Adjust { contrast, .. } if !non_negative(*contrast) => {
+                "the contrast must not be negative"
+            }
+            Transform::Gamma { gamma } if !positive(*gamma) => "the gamma must be positive",
+            Transform::Blur { sigma } if !positive(*sigma) => "the blur sigma must be positive",
+            Transform::Sharpen { sigma, .. } if !positive(*sigma) => {
+                "the sharpen radius must be positive"
+            }
+            Transform::Sharpen { amount, .. } if !non_negative(*amount) => {
+                "the sharpen amount must not be negative"
+            }
+            Transform::Sharpen { threshold, .. } if !non_negative(*threshold) => {
+                "the sharpen threshold must not be negative"
+            }
+            Transform::Denoise { radius, .. } if *radius == 0 => {
+                "the denoise radius must not be zero"
+            }
+            Transform::Vignette { strength, radius }
+                if !(0.0..=1.0).contains(strength) || !(0.0..=1.0).contains(radius) =>
+            {
+                "the vignette strength and radius must be from 0.0 to 1.0"
+            }
+            Transform::HueSaturation { hue, .. } if !hue.is_finite() => {
+                "the hue rotation must be a finite number"
+            }
+            Transform::HueSaturation { saturation, .. } if !non_negative(*saturation) => {
+                "the saturation factor must not be negative"
+            }
+            Transform::Pad { width, height, .. } if *width == 0 || *height == 0 => {
+                "the padded size must not be zero"
+            }
+            _ => return Ok(()),
+        };
+        Err(error.into())
+    }
+
+    /// Decode the watermark or render the text of the operation, so `apply` reuses them for
+    /// every image. The other operations have nothing to prepare.
+    pub fn prepare(&self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Transform::Watermark { path, stamp, .. } => stamp.get_or_prepare(|| read_image(path)),
+            Transform::Text {
+                text,
+                font,
+                size,
+                color,
+                stamp,
+                ..
+            } => stamp.get_or_prepare(|| render_text(text, font, *size, *color)),
+            _ => return Ok(()),
+        }
+        .map(|_| ())
+    }
+}
+
+/// `image` scaled down to fit inside `max_width` x `max_height`, smaller images are kept.
+///
+/// Large sources are first shrunk with a fast box filter to twice the thumbnail size,
+/// the remaining step uses Lanczos3 for a sharp result.
+fn thumbnail(image: &DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let scale = (max_width as f64 / width as f64)
+        .min(max_height as f64 / height as f64)
+        .min(1.0);
+    if scale == 1.0 {
+        return image.clone();
+    }
+    let thumbnail_width = ((width as f64 * scale).round() as u32).max(1);
+    let thumbnail_height = ((height as f64 * scale).round() as u32).max(1);
+    let (pre_width, pre_height) = (thumbnail_width * 2, thumbnail_height * 2);
+    let pre_shrunk = match pre_width < width && pre_height < height {
+        true => image.thumbnail_exact(pre_width, pre_height),
+        false => image.clone(),
+    };
+    pre_shrunk.resize_exact(thumbnail_width, thumbnail_height, FilterType::Lanczos3)
+}
+
+/// `image` rotated clockwise by `degrees`.
+///
+/// Multiples of 90 degrees move the pixels without resampling. Other angles enlarge the
+/// canvas to hold the whole rotated image and interpolate bilinearly, the corners are filled
+/// with `fill`. The result has an alpha channel if the source has one or `fill` is translucent.
+fn rotate(image: &DynamicImage, degrees: f64, fill: [u8; 4]) -> DynamicImage {
+    let degrees = degrees.rem_euclid(360.0);
+    match degrees {
+        0.0 => return image.clone(),
+        90.0 => return image.rotate90(),
+        180.0 => return image.rotate180(),
+        270.0 => return image.rotate270(),
+        _ => {}
+    }
+
+    let fill = color_of(image, fill);
+    let source = image.to_rgba32f();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    // The small tolerance keeps rounding errors from adding a row or column of fill.
+    let rotated_width = ((width * cos.abs() + height * sin.abs()) - 1e-6)
+        .ceil()
+        .max(1.0);
+    let rotated_height = ((width * sin.abs() + height * cos.abs()) - 1e-6)
+        .ceil()
+        .max(1.0);
+
+    let mut rotated = Rgba32FImage::new(rotated_width as u32, rotated_height as u32);
+    for (x, y, pixel) in rotated.enumerate_pixels_mut() {
+        // Rotate the pixel center back into the source, around the centers of both images.
+        let dx = x as f64 + 0.5 - rotated_width / 2.0;
+        let dy = y as f64 + 0.5 - rotated_height / 2.0;
+        let sx = dx * cos + dy * sin + width / 2.0 - 0.5;
+        let sy = -dx * sin + dy * cos + height / 2.0 - 0.5;
+        *pixel = bilinear(&source, sx, sy, fill);
+    }
+
+    let alpha = image.color().has_alpha() || fill.0[3] < 1.0;
+    with_sample_type_of(image, rotated, alpha)
+}
+
+/// `image` with `watermark` composited over it at `anchor`, inset by `margin` pixels.
+///
+/// `watermark` holds sRGB encoded samples, the alpha of each pixel is scaled by `opacity`.
+/// Parts of the watermark outside of the image are cut off.
+fn overlay(
+    image: &DynamicImage,
+    watermark: &Rgba32FImage,
+    anchor: Anchor,
+    margin: u32,
+    opacity: f32,
+) -> DynamicImage {
+    let float = is_float(image);
+    let mut base = image.to_rgba32f();
+    let free_x = base.width() as i64 - watermark.width() as i64;
+    let free_y = base.height() as i64 - watermark.height() as i64;
+    let margin = margin as i64;
+    let (x0, y0) = match anchor {
+        Anchor::TOP_LEFT => (margin, margin),
+        Anchor::TOP_RIGHT => (free_x - margin, margin),
+        Anchor::BOTTOM_LEFT => (margin, free_y - margin),
+        Anchor::BOTTOM_RIGHT => (free_x - margin, free_y - margin),
+        Anchor::CENTER => (free_x / 2, free_y / 2),
+    };
+    let opacity = opacity.clamp(0.0, 1.0);
+    for (x, y, mark) in watermark.enumerate_pixels() {
+        let (bx, by) = (x0 + x as i64, y0 + y as i64);
+        if bx < 0 || by < 0 {
+            continue;
+        }
+        let pixel = match base.get_pixel_mut_checked(bx as u32, by as u32) {
+            Some(p) => p,
+            None => continue,
+        };
+        let a = mark.0[3] * opacity;
+        let base_a = pixel.0[3] * (1.0 - a);
+        let out_a = a + base_a;
+        if out_a <= 0.0 {
+            continue;
+        }
+        for c in 0..3 {
+            let color = match float {
+                true => srgb::decode(mark.0[c]),
+                false => mark.0[c],
+            };
+            pixel.0[c] = (color * a + pixel.0[c] * base_a) / out_a;
+        }
+        pixel.0[3] = out_a;
+    }
+    with_sample_type_of(image, base, image.color().has_alpha())
+}
+
+/// `image` with `brightness` added to and `contrast` applied to its color channels.
+///
+/// Both work on sRGB encoded values so that the steps look even, float samples are
+/// encoded for it and keep values above `1.0`. Alpha is left as it is.
+fn adjust(image: &DynamicImage, brightness: i32, contrast: f32) -> DynamicImage {
+    let float = is_float(image);
+    let brightness = brightness as f32 / 255.0;
+    let mut adjusted = image.to_rgba32f();
+    for pixel in adjusted.pixels_mut() {
+        for sample in &mut pixel.0[..3] {
+            let encoded = match float {
+                true => srgb::encode(*sample),
+                false => *sample,
+            };
+            let value = ((encoded + brightness - 0.5) * contrast + 0.5).max(0.0);
+            *sample = match float {
+                true => srgb::decode(value),
+                false => value.min(1.0),
+            };
+        }
+    }
+    with_sample_type_of(image, adjusted, image.color().has_alpha())
+}
+
+/// `image` with `amount` times its difference from the blurred image added to it.
+///
+/// Pixels whose largest difference in a color channel does not exceed `threshold` are left
+/// as they are, which keeps smooth areas and noise from being sharpened. Alpha is left as it is.
+fn sharpen(image: &DynamicImage, sigma: f32, amount: f32, threshold: f32) -> DynamicImage {
+    let float = is_float(image);
+    let blurred = image.blur(sigma).to_rgba32f();
+    let mut sharpened = image.to_rgba32f();
+    for (pixel, blurred) in sharpened.pixels_mut().zip(blurred.pixels()) {
+        let detail = (0..3).fold(0.0f32, |d, c| d.max((pixel.0[c] - blurred.0[c]).abs()));
+        if detail <= threshold {
+            continue;
+        }
+        for (sample, blurred) in pixel.0[..3].iter_mut().zip(&blurred.0[..3]) {
+            let value = (*sample + (*sample - blurred) * amount).max(0.0);
+            *sample = match float {
+                true => value,
+                false => value.min(1.0),
+            };
+        }
+    }
+    with_sample_type_of(image, sharpened, image.color().has_alpha())
+}
+
+/// `image` with every color sample replaced by a mix of the samples up to `radius` pixels
+/// around it, on sRGB encoded values.
+///
+/// `Denoise::MEDIAN` takes the median of each channel. `Denoise::BILATERAL` averages
+/// the neighbours weighted by their distance and by how close their colors are, so that
+/// edges stay sharp. Pixels outside of the image repeat the edge. Alpha is left as it is.
+fn denoise(image: &DynamicImage, filter: Denoise, radius: u32) -> DynamicImage {
+    let float = is_float(image);
+    let mut source = image.to_rgba32f();
+    if float {
+        for pixel in source.pixels_mut() {
+            for sample in &mut pixel.0[..3] {
+                *sample = srgb::encode(sample.max(0.0));
+            }
+        }
+    }
+    let (width, height) = source.dimensions();
+    let radius = radius as i64;
+    let spatial_sigma = (radius as f32 / 2.0).max(0.5);
+    let neighbours = |x: u32, y: u32| {
+        (-radius..=radius).flat_map(move |dy| {
+            (-radius..=radius).map(move |dx| {
+                let nx = (x as i64 + dx).clamp(0, width as i64 - 1) as u32;
+                let ny = (y as i64 + dy).clamp(0, height as i64 - 1) as u32;
+                (dx, dy, nx, ny)
+            })
+        })
+    };
+
+    let mut denoised = source.clone();
+    for (x, y, pixel) in denoised.enumerate_pixels_mut() {
+        match filter {
+            Denoise::MEDIAN => {
+                let mut window: Vec<[f32; 4]> = neighbours(x, y)
+                    .map(|(_, _, nx, ny)| source.get_pixel(nx, ny).0)
+                    .collect();
+                let middle = window.len() / 2;
+                for c in 0..3 {
+                    window.select_nth_unstable_by(middle, |a, b| a[c].total_cmp(&b[c]));
+                    pixel.0[c] = window[middle][c];
+                }
+            }
+            Denoise::BILATERAL => {
+                let center = source.get_pixel(x, y).0;
+                let (mut sum, mut total) = ([0.0f32; 3], 0.0f32);
+                for (dx, dy, nx, ny) in neighbours(x, y) {
+                    let neighbour = source.get_pixel(nx, ny).0;
+                    let distance = (dx * dx + dy * dy) as f32;
+                    let difference: f32 = (0..3).map(|c| (neighbour[c] - center[c]).powi(2)).sum();
+                    let weight = (-distance / (2.0 * spatial_sigma * spatial_sigma)
+                        - difference / (2.0 * BILATERAL_RANGE_SIGMA * BILATERAL_RANGE_SIGMA))
+                        .exp();
+                    for c in 0..3 {
+                        sum[c] += neighbour[c] * weight;
+                    }
+                    total += weight;
+                }
+                for (sample, sum) in pixel.0[..3].iter_mut().zip(sum) {
+                    *sample = sum / total;
+                }
+            }
+        }
+        if float {
+            for sample in &mut pixel.0[..3] {
+                *sample = srgb::decode(*sample);
+            }
+        }
+    }
+    with_sample_type_of(image, denoised, image.color().has_alpha())
+}
+
+/// `image` with its colors scaled down towards the corners by up to `strength`.
+///
+/// The darkening rises smoothly from `radius` to the corners, both measured as the distance
+/// from the center relative to the distance of the corners. Alpha is left as it is.
+fn vignette(image: &DynamicImage, strength: f32, radius: f32) -> DynamicImage {
+    let mut darkened = image.to_rgba32f();
+    let center_x = darkened.width() as f32 / 2.0;
+    let center_y = darkened.height() as f32 / 2.0;
+    let corner = center_x.hypot(center_y);
+    for (x, y, pixel) in darkened.enumerate_pixels_mut() {
+        let distance = (x as f32 + 0.5 - center_x).hypot(y as f32 + 0.5 - center_y) / corner;
+        let t = ((distance - radius) / (1.0 - radius).max(f32::EPSILON)).clamp(0.0, 1.0);
+        let factor = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+        for sample in &mut pixel.0[..3] {
+            *sample *= factor;
+        }
+    }
+    with_sample_type_of(image, darkened, image.color().has_alpha())
+}
+
+/// `image` with its HSL hue rotated by `hue` degrees and its HSL saturation scaled by
+/// `saturation`, both on sRGB encoded values.
+///
+/// Float samples above `1.0` are scaled into range for it and back. Alpha is left as it is.
+fn hue_saturation(image: &DynamicImage, hue: f32, saturation: f32) -> DynamicImage {
+    let float = is_float(image);
+    let mut adjusted = image.to_rgba32f();
+    for pixel in adjusted.pixels_mut() {
+        let mut rgb = [0, 1, 2].map(|c| match float {
+            true => srgb::encode(pixel.0[c].max(0.0)),
+            false => pixel.0[c],
+        });
+        let scale = rgb.iter().fold(1.0f32, |m, s| m.max(*s));
+        rgb = rgb.map(|s| s / scale);
+
+        let [h, s, l] = to_hsl(rgb);
+        rgb = from_hsl([(h + hue).rem_euclid(360.0), (s * saturation).min(1.0), l]);
+
+        for (c, value) in rgb.into_iter().enumerate() {
+            pixel.0[c] = match float {
+                true => srgb::decode(value * scale),
+                false => value,
+            };
+        }
+    }
+    with_sample_type_of(image, adjusted, image.color().has_alpha())
+}
+
+/// The hue in degrees, saturation and lightness of the RGB color `rgb` from `0.0` to `1.0`.
+fn to_hsl([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let chroma = max - min;
+    if chroma <= 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+    let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = match max {
+        m if m == r => ((g - b) / chroma).rem_euclid(6.0),
+        m if m == g => (b - r) / chroma + 2.0,
+        _ => (r - g) / chroma + 4.0,
+    };
+    [hue * 60.0, saturation, lightness]
+}
+
+/// The RGB color of the hue in degrees, saturation and lightness `hsl`.
+fn from_hsl([hue, saturation, lightness]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r + m, g + m, b + m].map(|s| s.clamp(0.0, 1.0))
+}
+
+/// `image` centered on a `width` x `height` canvas of `color`.
+///
+/// Larger images are scaled down to fit first, keeping their aspect ratio.
+/// The result has an alpha channel if the source has one or `color` is translucent.
+fn pad(image: &DynamicImage, width: u32, height: u32, color: [u8; 4]) -> DynamicImage {
+    let fitted = thumbnail(image, width, height);
+    let x0 = (width - fitted.width()) / 2;
+    let y0 = (height - fitted.height()) / 2;
+    place(&fitted, width, height, x0, y0, color)
+}
+
+/// `image` with its top left corner at `x0`, `y0` of a `width` x `height` canvas of `color`.
+///
+/// The result has an alpha channel if the source has one or `color` is translucent.
+fn place(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    x0: u32,
+    y0: u32,
+    color: [u8; 4],
+) -> DynamicImage {
+    let fill = color_of(image, color);
+    let mut canvas = Rgba32FImage::from_pixel(width, height, fill);
+    for (x, y, pixel) in image.to_rgba32f().enumerate_pixels() {
+        canvas.put_pixel(x0 + x, y0 + y, *pixel);
+    }
+    let alpha = image.color().has_alpha() || fill.0[3] < 1.0;
+    with_sample_type_of(image, canvas, alpha)
+}
+
+/// The 8 bit RGBA `color` as samples of `image`, linear for float images.
+fn color_of(image: &DynamicImage, color: [u8; 4]) -> Rgba<f32> {
+    let float = is_float(image);
+    Rgba([0, 1, 2, 3].map(|c| match (float, c) {
+        (true, 0..=2) => srgb::decode(color[c] as f32 / 255.0),
+        _ => color[c] as f32 / 255.0,
+    }))
+}
+
+/// `image` without the rows and columns at its edges that only hold border pixels.
+///
+/// Border pixels are transparent, or match the top left pixel within `TRIM_TOLERANCE`.
+/// An image that is border only is returned as it is.
+fn trim(image: &DynamicImage) -> DynamicImage {
+    let pixels = image.to_rgba32f();
+    let corner = *pixels.get_pixel(0, 0);
+    let is_border = |x: u32, y: u32| {
+        let pixel = pixels.get_pixel(x, y);
+        pixel.0[3] <= 0.0
+            || pixel
+                .0
+                .iter()
+                .zip(&corner.0)
+                .all(|(s, c)| (s - c).abs() <= TRIM_TOLERANCE)
+    };
+    let (width, height) = pixels.dimensions();
+    let content_row = |y: u32| (0..width).any(|x| !is_border(x, y));
+    let content_column = |x: u32| (0..height).any(|y| !is_border(x, y));
+    let top = match (0..height).find(|y| content_row(*y)) {
+        Some(t) => t,
+        None => return image.clone(),
+    };
+    let bottom = (0..height).rev().find(|y| content_row(*y)).unwrap_or(top);
+    let left = (0..width).find(|x| content_column(*x)).unwrap_or(0);
+    let right = (0..width)
+        .rev()
+        .find(|x| content_column(*x))
+        .unwrap_or(left);
+    image.crop_imm(left, top, right - left + 1, bottom - top + 1)
+}
+
+/// `image` with every color sample `v` from `0.0` to `1.0` raised to `1 / gamma`.
+///
+/// Float samples are raised as they are stored, so values above `1.0` are kept.
+/// Alpha is left as it is.
+fn correct_gamma(image: &DynamicImage, gamma: f32) -> DynamicImage {
+    let exponent = 1.0 / gamma;
+    let mut corrected = image.to_rgba32f();
+    for pixel in corrected.pixels_mut() {
+        for sample in &mut pixel.0[..3] {
+            *sample = sample.max(0.0).powf(exponent);
+        }
+    }
+    with_sample_type_of(image, corrected, image.color().has_alpha())
+}
+
+/// `image` with `map` called on the 8 bit sRGB encoded samples of every pixel.
+///
+/// Deeper samples are reduced to 8 bits but keep their type, float samples are decoded back
+/// to linear light. An alpha channel is added if the map makes a pixel translucent.
+fn map_pixels(image: &DynamicImage, map: &PixelMap) -> DynamicImage {
+    let mut samples = to_srgb(image).to_rgba8();
+    let mut translucent = false;
+    for pixel in samples.pixels_mut() {
+        (map.0)(&mut pixel.0);
+        translucent |= pixel.0[3] < 255;
+    }
+    let mut mapped = DynamicImage::ImageRgba8(samples).to_rgba32f();
+    if is_float(image) {
+        for pixel in mapped.pixels_mut() {
+            for sample in &mut pixel.0[..3] {
+                *sample = srgb::decode(*sample);
+            }
+        }
+    }
+    with_sample_type_of(image, mapped, image.color().has_alpha() || translucent)
+}
+
+/// `rgba` with the sample type of `source`, with or without its `alpha` channel.
+fn with_sample_type_of(source: &DynamicImage, rgba: Rgba32FImage, alpha: bool) -> DynamicImage {
+    let d = DynamicImage::ImageRgba32F(rgba);
+    match (is_float(source), is_16bit(source), alpha) {
+        (true, _, true) => d,
+        (true, _, false) => DynamicImage::ImageRgb32F(d.to_rgb32f()),
+        (false, true, true) => DynamicImage::ImageRgba16(d.to_rgba16()),
+        (false, true, false) => DynamicImage::ImageRgb16(d.to_rgb16()),
+        (false, false, true) => DynamicImage::ImageRgba8(d.to_rgba8()),
+        (false, false, false) => DynamicImage::ImageRgb8(d.to_rgb8()),
+    }
+}
+
+/// The sRGB encoded RGBA samples of the watermark `image`.
+fn watermark_samples(image: &DynamicImage) -> Rgba32FImage {
+    to_srgb(image).to_rgba32f()
+}
+
+/// The color of `image` at `x`, `y` interpolated from the four surrounding pixels,
+/// pixels outside of the image having the color `fill`.
+fn bilinear(image: &Rgba32FImage, x: f64, y: f64, fill: Rgba<f32>) -> Rgba<f32> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+    let at = |px: f64, py: f64| {
+        if px < 0.0 || py < 0.0 {
+            return fill;
+        }
+        match image.get_pixel_checked(px as u32, py as u32) {
+            Some(p) => *p,
+            None => fill,
+        }
+    };
+    let corners = [
+        (at(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (at(x0 + 1.0, y0), fx * (1.0 - fy)),
+        (at(x0, y0 + 1.0), (1.0 - fx) * fy),
+        (at(x0 + 1.0, y0 + 1.0), fx * fy),
+    ];
+    let mut color = [0f32; 4];
+    for (pixel, weight) in corners {
+        for (sum, sample) in color.iter_mut().zip(pixel.0) {
+            *sum += sample * weight;
+        }
+    }
+    Rgba(color)
+}
+
+/// The filter of the `image` crate implementing `filter`.
+fn filter_type(filter: ResizeFilter) -> FilterType {
+    match filter {
+        ResizeFilter::NEAREST => FilterType::Nearest,
+        ResizeFilter::BILINEAR => FilterType::Triangle,
+        ResizeFilter::LANCZOS3 => FilterType::Lanczos3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::{trim, Stamp, Transform};
+    use crate::imfconv::Anchor;
+
+    #[test]
+    fn trim_removes_transparent_margins_next_to_an_opaque_corner() {
+        let mut image = RgbaImage::from_pixel(10, 6, Rgba([255, 255, 255, 255]));
+        for y in 0..6 {
+            for x in 7..10 {
+                image.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+            }
+        }
+        image.put_pixel(3, 2, Rgba([0, 0, 255, 255]));
+        let trimmed = trim(&DynamicImage::ImageRgba8(image));
+        assert_eq!((trimmed.width(), trimmed.height()), (1, 1));
+    }
+
+    #[test]
+    fn watermark_is_decoded_once_for_every_copy() {
+        let path = std::env::temp_dir().join(format!("imfconv-mark-{}.png", std::process::id()));
+        RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+        let watermark = Transform::Watermark {
+            path: path.clone(),
+            anchor: Anchor::TOP_LEFT,
+            margin: 0,
+            opacity: 1.0,
+            stamp: Stamp::default(),
+        };
+        watermark.prepare().unwrap();
+        let copy = watermark.clone();
+        fs::remove_file(&path).unwrap();
+
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(4, 4));
+        let marked = copy.apply(&image).unwrap().to_rgba8();
+        assert_eq!(*marked.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(*marked.get_pixel(3, 3), Rgba([0, 0, 0, 0]));
+
+        let missing = Transform::Watermark {
+            path,
+            anchor: Anchor::TOP_LEFT,
+            margin: 0,
+            opacity: 1.0,
+            stamp: Stamp::default(),
+        };
+        assert!(missing.prepare().is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+/// Callback the progress of a conversion is reported to.
+pub(crate) type ProgressFn = dyn Fn(Progress) + Send + Sync;
+
+/// Stages every conversion goes through, in this order until it fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// Reading the pixels of a source.
+    DECODING,
+    /// Applying the operations, the color space and the color profile to a source.
+    TRANSFORMING,
+    /// Encoding and writing the output files.
+    ENCODING,
+    /// The output is complete.
+    DONE,
+    /// The conversion failed, reported instead of `DONE` by batches only.
+    FAILED,
+}
+
+/// Progress of a conversion reported to the callback of `set_progress_callback`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// The source the stage applies to, the first source for the stages of conversions
+    /// combining several. Empty for sources given as bytes.
+    pub source: PathBuf,
+    pub stage: Stage,
+    /// Files of the batch finished so far, converted or failed, counting this one once it is
+    /// `DONE` or `FAILED`.
+    pub completed: usize,
+    /// Files in the batch, 1 for a single conversion.
+    pub total: usize,
+}
@@ -1,22 +1,657 @@
-use std::{error::Error, path::Path};
+use std::{
+    error::Error,
+    fmt::Error as FmtError,
+    fs::File,
+    io::{Cursor, Read},
+    ops::Deref,
+    path::Path,
+};
 
-use image::io::Reader;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder},
+    imageops,
+    io::Reader,
+    AnimationDecoder, DynamicImage, ImageBuffer, ImageFormat, RgbImage, RgbaImage,
+};
+use memmap2::Mmap;
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    ColorType,
+};
+
+use super::handler::animation::{Animation, AnimationFrame};
+
+mod exif;
+#[cfg(feature = "http")]
+mod http;
+mod icc;
+mod iptc;
+#[cfg(feature = "raw")]
+mod raw;
+mod svg;
+mod xmp;
+
+pub use exif::{orient, read_exif, read_orientation};
+#[cfg(feature = "http")]
+pub use http::fetch;
+pub use icc::read_icc_profile;
+pub use iptc::read_iptc;
+pub use svg::is_svg;
+pub use xmp::read_xmp;
+
+/// Magic bytes at the start of every QOI file.
+const QOI_MAGIC: &[u8] = b"qoif";
+/// Magic bytes at the start of every Photoshop document.
+const PSD_MAGIC: &[u8] = b"8BPS";
+/// Magic bytes of little and big endian TIFF files.
+const TIFF_MAGIC: [&[u8]; 2] = [b"II*\0", b"MM\0*"];
+/// Signature at the start of every PNG file.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Size in bytes from which source files are mapped into memory instead of being read.
+const MAP_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// Bytes at the start of a file read to tell whether it is an image.
+const SNIFF_LENGTH: u64 = 4096;
+
+/// Contents of a source file, read into memory or mapped into memory for large files.
+pub enum FileData {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Read(d) => d,
+            FileData::Mapped(m) => m,
+        }
+    }
+}
+
+/// Read the contents of the file located at `src`.
+///
+/// Files of `MAP_THRESHOLD` bytes or more, such as large TIFF scans, are mapped into memory,
+/// so only the parts the decoder and the metadata readers touch are loaded. The metadata
+/// readers walk the headers, chunks or directories of the file without copying it.
+/// Mapped files must not be changed while they are mapped.
+pub fn read_file(src: &Path) -> Result<FileData, Box<dyn Error>> {
+    let mut file = match File::open(src) {
+        Ok(f) => f,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let size = match file.metadata() {
+        Ok(m) => m.len(),
+        Err(e) => return Err(Box::new(e)),
+    };
+    if size >= MAP_THRESHOLD {
+        // SAFETY: the mapping is only read, sources are not expected to be written to
+        // while they are converted.
+        if let Ok(m) = unsafe { Mmap::map(&file) } {
+            return Ok(FileData::Mapped(m));
+        }
+    }
+    let mut data = Vec::with_capacity(size as usize);
+    match file.read_to_end(&mut data) {
+        Ok(_) => Ok(FileData::Read(data)),
+        Err(e) => Err(Box::new(e)),
+    }
+}
 
 /// Read the image located at `src` specified.
 ///
-/// The image format is interpreted from memory block of the image.
+/// See `decode` for the formats that are recognized.
+pub fn read_image(src: &Path) -> Result<DynamicImage, Box<dyn Error>> {
+    decode(src, &read_file(src)?)
+}
+
+/// Read every page of the image file `data` read from `src`.
 ///
-/// Returns `Ok(width: u32, height: u32, image: Vec<u8>)`
-pub fn read_image(src: &Path) -> Result<(u32, u32, Vec<u8>), Box<dyn Error>> {
-    let reader = match Reader::open(src) {
-        Ok(r) => match r.with_guessed_format() {
-            Ok(r) => r,
-            Err(e) => return Err(Box::new(e)),
+/// Multi-page TIFF sources return one image per page, other sources a single image.
+pub fn read_pages(src: &Path, data: &[u8]) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+    let is_tiff = TIFF_MAGIC.iter().any(|m| data.starts_with(m));
+    #[cfg(feature = "raw")]
+    let is_tiff = is_tiff && !raw::is_raw(src, data);
+    if !is_tiff {
+        return Ok(vec![decode(src, data)?]);
+    }
+    read_tiff_pages(data)
+}
+
+/// Read every frame of the animated GIF or PNG image file `data`.
+///
+/// Returns `None` for still images and for formats without animation.
+pub fn read_animation(data: &[u8]) -> Result<Option<Animation>, Box<dyn Error>> {
+    let format = match image::guess_format(data) {
+        Ok(f) => f,
+        Err(_) => return Ok(None),
+    };
+    let cursor = Cursor::new(data);
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(cursor).map(|d| d.into_frames()),
+        ImageFormat::Png => match PngDecoder::new(cursor) {
+            Ok(d) if d.is_apng() => Ok(d.apng().into_frames()),
+            Ok(_) => return Ok(None),
+            Err(e) => Err(e),
         },
+        _ => return Ok(None),
+    };
+    let frames = match frames.and_then(|f| f.collect_frames()) {
+        Ok(f) => f,
+        Err(e) => return Err(Box::new(e)),
+    };
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    // Frames covering only part of the canvas are placed at their offset.
+    let (width, height) = frames[0].buffer().dimensions();
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let (left, top) = (frame.left(), frame.top());
+            let mut buffer = frame.into_buffer();
+            if buffer.dimensions() != (width, height) || left != 0 || top != 0 {
+                let mut canvas = RgbaImage::new(width, height);
+                imageops::overlay(&mut canvas, &buffer, left.into(), top.into());
+                buffer = canvas;
+            }
+            AnimationFrame {
+                image: DynamicImage::ImageRgba8(buffer),
+                delay_ms: (numer + denom / 2) / denom.max(1),
+            }
+        })
+        .collect();
+    Ok(Some(Animation {
+        frames,
+        loop_count: loop_count(data, format),
+    }))
+}
+
+/// Number of plays stored in an animated GIF or PNG file, 0 repeats forever.
+fn loop_count(data: &[u8], format: ImageFormat) -> u16 {
+    let find = |tag: &[u8]| {
+        data.windows(tag.len())
+            .position(|w| w == tag)
+            .map(|p| p + tag.len())
+    };
+    match format {
+        // GIF animations without the NETSCAPE2.0 extension are played once.
+        ImageFormat::Gif => match find(b"NETSCAPE2.0\x03\x01").and_then(|p| data.get(p..p + 2)) {
+            Some(b) => u16::from_le_bytes([b[0], b[1]]),
+            None => 1,
+        },
+        // acTL holds the frame count followed by the number of plays.
+        ImageFormat::Png => match find(b"acTL").and_then(|p| data.get(p + 4..p + 8)) {
+            Some(b) => u32::from_be_bytes([b[0], b[1], b[2], b[3]]).min(u16::MAX.into()) as u16,
+            None => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Marker and contents of the JPEG segments in `data` up to the start of the image data.
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = Vec::new();
+    // Skip the start of image marker.
+    let mut position = 2;
+    while let Some(&[0xff, marker]) = data.get(position..position + 2) {
+        match marker {
+            // Fill bytes may precede a marker.
+            0xff => position += 1,
+            // Markers without a length.
+            0x01 | 0xd0..=0xd7 => position += 2,
+            // The image data or the end of image follows.
+            0xd9 | 0xda => break,
+            _ => {
+                let length = match data.get(position + 2..position + 4) {
+                    Some(l) => u16::from_be_bytes([l[0], l[1]]) as usize,
+                    None => break,
+                };
+                match data.get(position + 4..position + 2 + length.max(2)) {
+                    Some(s) => segments.push((marker, s)),
+                    None => break,
+                }
+                position += 2 + length;
+            }
+        }
+    }
+    segments
+}
+
+/// Type and contents of the chunks of the PNG file `data` up to the start of the image data.
+fn png_chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+    while let Some(header) = data.get(position..position + 8) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if &header[4..] == b"IDAT" {
+            break;
+        }
+        match data.get(position + 8..position + 8 + length) {
+            Some(body) => chunks.push((&header[4..], body)),
+            None => break,
+        }
+        // Length, type, body and CRC.
+        position += 12 + length;
+    }
+    chunks
+}
+
+/// Type and contents of the chunks of the RIFF file `data`, such as a WebP file.
+fn riff_chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    // Skip the RIFF header, the file size and the form type.
+    let mut position = 12;
+    while let Some(header) = data.get(position..position + 8) {
+        let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        match data.get(position + 8..position + 8 + length) {
+            Some(body) => chunks.push((&header[..4], body)),
+            None => break,
+        }
+        // Chunks are padded to an even length.
+        position += 8 + length + length % 2;
+    }
+    chunks
+}
+
+/// Decode the image file `data` read from `src`.
+///
+/// The image format is interpreted from memory block of the image, the extension of `src`
+/// is only used for formats without a signature, such as TGA, and never overrides it.
+/// A `.jpg` file holding a PNG image is decoded as PNG.
+///
+/// SVG documents are rasterized at their own size, see `rasterize_svg` for other sizes.
+/// Photoshop documents are read from the flattened composite they store.
+/// Camera RAW files are recognized by their extension and developed to linear float RGB.
+///
+/// Returns the decoded image with its original sample type,
+/// e.g. OpenEXR sources keep their float data.
+pub fn decode(src: &Path, data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    if is_svg(src, data) {
+        return svg::read_svg(src, data, None);
+    }
+    // QOI is not known to the `image` crate, so it is decoded separately.
+    if data.starts_with(QOI_MAGIC) {
+        return read_qoi(data);
+    }
+    if data.starts_with(PSD_MAGIC) {
+        return read_psd(data);
+    }
+    #[cfg(feature = "heif")]
+    if libheif_rs::check_file_type(data) == libheif_rs::FileTypeResult::Supported {
+        return read_heif(data);
+    }
+    // Most RAW formats are TIFF based, so they have to be picked before guessing the format.
+    #[cfg(feature = "raw")]
+    if raw::is_raw(src, data) {
+        return raw::read_raw(data);
+    }
+
+    let mut reader = Reader::new(Cursor::new(data));
+    if let Ok(format) = ImageFormat::from_path(src) {
+        reader.set_format(format);
+    }
+    let reader = match reader.with_guessed_format() {
+        Ok(r) => r,
         Err(e) => return Err(Box::new(e)),
     };
     match reader.decode() {
-        Ok(d) => return Ok((d.width(), d.height(), Vec::from(d.as_bytes()))),
-        Err(e) => panic!("{:?}", e),
+        Ok(d) => Ok(d),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Check that the image file `data` read from `src` is in a format that `decode` reads,
+/// reading the header of the image only.
+///
+/// Returns an `Error` if the format is unknown or the header cannot be read. Errors in the
+/// image data itself are only found by `decode`.
+pub fn probe(src: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    if is_svg(src, data) || data.starts_with(PSD_MAGIC) {
+        return Ok(());
+    }
+    if data.starts_with(QOI_MAGIC) {
+        return match qoi::decode_header(data) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        };
+    }
+    #[cfg(feature = "heif")]
+    if libheif_rs::check_file_type(data) == libheif_rs::FileTypeResult::Supported {
+        return Ok(());
+    }
+    #[cfg(feature = "raw")]
+    if raw::is_raw(src, data) {
+        return Ok(());
+    }
+
+    let mut reader = Reader::new(Cursor::new(data));
+    if let Ok(format) = ImageFormat::from_path(src) {
+        reader.set_format(format);
+    }
+    let reader = match reader.with_guessed_format() {
+        Ok(r) => r,
+        Err(e) => return Err(Box::new(e)),
     };
+    match reader.into_dimensions() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Whether the file located at `src` is in a format that `decode` reads, judged from the
+/// start of the file only, so damaged images still count as images.
+pub fn is_image(src: &Path) -> bool {
+    let mut start = Vec::new();
+    let read = File::open(src).and_then(|f| f.take(SNIFF_LENGTH).read_to_end(&mut start));
+    if read.is_err() {
+        return false;
+    }
+    if is_svg(src, &start) || start.starts_with(QOI_MAGIC) || start.starts_with(PSD_MAGIC) {
+        return true;
+    }
+    #[cfg(feature = "heif")]
+    if libheif_rs::check_file_type(&start) == libheif_rs::FileTypeResult::Supported {
+        return true;
+    }
+    #[cfg(feature = "raw")]
+    if raw::is_raw(src, &start) {
+        return true;
+    }
+    image::guess_format(&start).is_ok()
+}
+
+/// Rasterize the SVG document `data` read from `src` to `width` x `height` pixels.
+pub fn rasterize_svg(
+    src: &Path,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    svg::read_svg(src, data, Some((width, height)))
+}
+
+/// Decode every page of a TIFF file with its original sample type.
+fn read_tiff_pages(data: &[u8]) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+    let mut decoder = match Decoder::new(Cursor::new(data)) {
+        Ok(d) => d,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let mut pages = Vec::new();
+    loop {
+        let (w, h) = match decoder.dimensions() {
+            Ok(d) => d,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let color_type = match decoder.colortype() {
+            Ok(c) => c,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let samples = match decoder.read_image() {
+            Ok(s) => s,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let page = match (color_type, samples) {
+            (ColorType::Gray(8), DecodingResult::U8(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageLuma8)
+            }
+            (ColorType::Gray(16), DecodingResult::U16(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageLuma16)
+            }
+            (ColorType::GrayA(8), DecodingResult::U8(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageLumaA8)
+            }
+            (ColorType::GrayA(16), DecodingResult::U16(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageLumaA16)
+            }
+            (ColorType::RGB(8), DecodingResult::U8(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgb8)
+            }
+            (ColorType::RGB(16), DecodingResult::U16(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgb16)
+            }
+            (ColorType::RGB(32), DecodingResult::F32(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgb32F)
+            }
+            (ColorType::RGBA(8), DecodingResult::U8(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgba8)
+            }
+            (ColorType::RGBA(16), DecodingResult::U16(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgba16)
+            }
+            (ColorType::RGBA(32), DecodingResult::F32(s)) => {
+                ImageBuffer::from_raw(w, h, s).map(DynamicImage::ImageRgba32F)
+            }
+            (c, _) => return Err(format!("TIFF pages of type {:?} are not supported", c).into()),
+        };
+        match page {
+            Some(p) => pages.push(p),
+            None => return Err(Box::new(FmtError)),
+        }
+
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        if let Err(e) = decoder.next_image() {
+            return Err(Box::new(e));
+        }
+    }
+}
+
+/// Decode a QOI image to RGB, or to RGBA if it has an alpha channel.
+fn read_qoi(data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    let mut decoder = match qoi::Decoder::new(data) {
+        Ok(d) => d,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let header = *decoder.header();
+    let pixels = match decoder.decode_to_vec() {
+        Ok(i) => i,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let image = match header.channels {
+        qoi::Channels::Rgba => {
+            RgbaImage::from_vec(header.width, header.height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        qoi::Channels::Rgb => {
+            RgbImage::from_vec(header.width, header.height, pixels).map(DynamicImage::ImageRgb8)
+        }
+    };
+    match image {
+        Some(i) => Ok(i),
+        None => Err(Box::new(FmtError)),
+    }
+}
+
+/// Decode the composite image of a Photoshop document to RGBA.
+///
+/// Photoshop stores the composite when "Maximize Compatibility" is enabled,
+/// the layers themselves are not flattened here.
+fn read_psd(data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    let psd = match psd::Psd::from_bytes(data) {
+        Ok(p) => p,
+        Err(e) => return Err(Box::new(e)),
+    };
+    match RgbaImage::from_vec(psd.width(), psd.height(), psd.rgba()) {
+        Some(i) => Ok(DynamicImage::ImageRgba8(i)),
+        None => Err("the PSD file has no composite image".into()),
+    }
+}
+
+/// Decode the primary image of a HEIF/HEIC container to RGB.
+#[cfg(feature = "heif")]
+fn read_heif(data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let lib_heif = LibHeif::new();
+    let context = match HeifContext::read_from_bytes(data) {
+        Ok(c) => c,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let handle = match context.primary_image_handle() {
+        Ok(h) => h,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let image = match lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None) {
+        Ok(i) => i,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let plane = match image.planes().interleaved {
+        Some(p) => p,
+        None => return Err(Box::new(FmtError)),
+    };
+
+    // Rows of the decoded plane may be padded, so copy only the pixel data of each row.
+    let row_len = plane.width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    match RgbImage::from_vec(plane.width, plane.height, pixels) {
+        Some(i) => Ok(DynamicImage::ImageRgb8(i)),
+        None => Err(Box::new(FmtError)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use exif::{experimental::Writer, Field, In, Reader, Tag, Value};
+    use flate2::{write::ZlibEncoder, Compression};
+    use image::{DynamicImage, RgbImage};
+
+    use super::{
+        png_chunks, read_exif, read_icc_profile, read_orientation, read_xmp, riff_chunks,
+        PNG_SIGNATURE,
+    };
+    use crate::imfconv::handler::{
+        format::{tiff::TiffHandler, EncodeOptions, ImfconvHandler},
+        metadata::Metadata,
+    };
+
+    /// A PNG chunk of `kind`, with a dummy CRC the readers do not check.
+    fn png_chunk(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(body);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    /// A RIFF chunk of `fourcc`, padded to an even length.
+    fn riff_chunk(fourcc: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn png_chunks_stop_at_the_image_data() {
+        let mut iccp = b"profile\0\0".to_vec();
+        iccp.extend(zlib(b"icc bytes"));
+        let mut itxt = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+        itxt.extend_from_slice(b"<x:xmpmeta/>");
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(png_chunk(b"IHDR", &[0; 13]));
+        data.extend(png_chunk(b"iCCP", &iccp));
+        data.extend(png_chunk(b"iTXt", &itxt));
+        data.extend(png_chunk(b"IDAT", b"pixels"));
+        data.extend(png_chunk(b"tEXt", b"after the image data"));
+
+        let kinds: Vec<&[u8]> = png_chunks(&data).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"iCCP", b"iTXt"]);
+        assert_eq!(read_icc_profile(&data).unwrap(), b"icc bytes");
+        assert_eq!(read_xmp(&data).unwrap(), b"<x:xmpmeta/>");
+
+        // A chunk cut short ends the walk instead of reading past the end.
+        assert_eq!(png_chunks(&data[..PNG_SIGNATURE.len() + 35]).len(), 1);
+    }
+
+    #[test]
+    fn riff_chunks_skip_the_padding() {
+        let chunks = [
+            riff_chunk(b"VP8X", &[0; 10]),
+            riff_chunk(b"ICCP", b"odd"),
+            riff_chunk(b"XMP ", b"<x:xmpmeta/>"),
+        ]
+        .concat();
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+        data.extend_from_slice(b"WEBP");
+        data.extend(chunks);
+
+        let fourccs: Vec<&[u8]> = riff_chunks(&data).into_iter().map(|(f, _)| f).collect();
+        assert_eq!(fourccs, [&b"VP8X"[..], b"ICCP", b"XMP "]);
+        assert_eq!(read_icc_profile(&data).unwrap(), b"odd");
+        assert_eq!(read_xmp(&data).unwrap(), b"<x:xmpmeta/>");
+    }
+
+    #[test]
+    fn tiff_directories_are_read_within_the_file() {
+        let fields = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            Field {
+                tag: Tag::DateTimeOriginal,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"2024:01:02 03:04:05".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![(35, 1).into(), (41, 1).into(), (2230, 100).into()]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut block = Cursor::new(Vec::new());
+        writer.write(&mut block, false).unwrap();
+        let options = EncodeOptions {
+            metadata: Metadata {
+                exif: Some(block.into_inner()),
+                xmp: Some(b"<x:xmpmeta/>".to_vec()),
+                ..Metadata::default()
+            },
+            ..EncodeOptions::default()
+        };
+        let image = DynamicImage::ImageRgb8(RgbImage::new(8, 8));
+        let data = TiffHandler.encode(&image, &options).unwrap().data;
+
+        assert_eq!(read_orientation(&data), 6);
+        assert_eq!(read_xmp(&data).unwrap(), b"<x:xmpmeta/>");
+        let exif = Reader::new().read_raw(read_exif(&data).unwrap()).unwrap();
+        for field in &fields {
+            let read = exif.get_field(field.tag, In::PRIMARY).unwrap();
+            assert_eq!(
+                read.display_value().to_string(),
+                field.display_value().to_string()
+            );
+        }
+
+        // Directories and values cut off by the end of the file are left out.
+        for length in 0..data.len() {
+            let data = &data[..length];
+            assert!([1, 6].contains(&read_orientation(data)));
+            read_exif(data);
+            read_xmp(data);
+        }
+    }
 }
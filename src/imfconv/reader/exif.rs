@@ -0,0 +1,298 @@
+use std::{borrow::Cow, io::Cursor};
+
+use exif::{experimental::Writer, Context, Field, In, Reader, Tag, Value};
+use image::DynamicImage;
+
+use super::TIFF_MAGIC;
+use crate::imfconv::handler::metadata::DESCRIPTIVE_TIFF_TAGS;
+
+/// TIFF field type of 16 bit unsigned integers.
+const SHORT: u16 = 3;
+/// TIFF field type of 32 bit unsigned integers.
+const LONG: u16 = 4;
+/// Directories referenced from the first page of TIFF based sources, with the context of
+/// their fields. The interoperability directory is referenced from the EXIF directory.
+const SUB_DIRECTORIES: [(Tag, Context); 3] = [
+    (Tag::ExifIFDPointer, Context::Exif),
+    (Tag::GPSInfoIFDPointer, Context::Gps),
+    (Tag::InteropIFDPointer, Context::Interop),
+];
+
+/// Read the EXIF block of the image file `data`, starting with its TIFF header.
+///
+/// JPEG, PNG, WebP and HEIF sources return the block as they store it.
+/// The EXIF fields of TIFF based sources are part of the image file directory itself,
+/// so a new block is built from the fields of the first page, leaving out the fields
+/// that describe the layout of the image data. Only the directories and their values
+/// are read, not the whole file.
+///
+/// Returns `None` if the source has no EXIF block or the block cannot be parsed.
+pub fn read_exif(data: &[u8]) -> Option<Vec<u8>> {
+    let Some(tiff) = TiffFile::new(data) else {
+        return match Reader::new().read_from_container(&mut Cursor::new(data)) {
+            Ok(e) => Some(e.buf().to_vec()),
+            Err(_) => None,
+        };
+    };
+
+    let first_page = tiff.first_directory()?;
+    let mut fields: Vec<Field> = first_page
+        .iter()
+        .filter(|e| DESCRIPTIVE_TIFF_TAGS.contains(&Tag(Context::Tiff, e.tag)))
+        .filter_map(|e| tiff.field(e, Context::Tiff))
+        .collect();
+    let mut directories = first_page;
+    for (pointer, context) in SUB_DIRECTORIES {
+        let Some(offset) = directories
+            .iter()
+            .find(|e| e.tag == pointer.number())
+            .and_then(|e| tiff.uint(e))
+        else {
+            continue;
+        };
+        let directory = tiff.directory(offset).unwrap_or_default();
+        fields.extend(directory.iter().filter_map(|e| tiff.field(e, context)));
+        directories.extend(directory);
+    }
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut block = Cursor::new(Vec::new());
+    match writer.write(&mut block, tiff.little_endian) {
+        Ok(_) => Some(block.into_inner()),
+        Err(_) => None,
+    }
+}
+
+/// Read the EXIF orientation of the image file `data`, from 1 to 8.
+///
+/// Returns 1, the orientation of unrotated pixels, if the source does not specify one.
+/// HEIF sources also return 1, since libheif already applies their rotation and mirroring
+/// when decoding.
+pub fn read_orientation(data: &[u8]) -> u16 {
+    #[cfg(feature = "heif")]
+    if libheif_rs::check_file_type(data) == libheif_rs::FileTypeResult::Supported {
+        return 1;
+    }
+    let orientation = match TiffFile::new(data) {
+        Some(tiff) => tiff.first_directory().and_then(|d| {
+            d.iter()
+                .find(|e| e.tag == Tag::Orientation.number())
+                .and_then(|e| tiff.uint(e))
+        }),
+        None => Reader::new()
+            .read_from_container(&mut Cursor::new(data))
+            .ok()
+            .and_then(|e| {
+                e.get_field(Tag::Orientation, In::PRIMARY)
+                    .and_then(|f| f.value.get_uint(0))
+            }),
+    };
+    match orientation {
+        Some(o @ 1..=8) => o as u16,
+        _ => 1,
+    }
+}
+
+/// Rotate and flip `image` so that it is displayed upright, given its EXIF `orientation`.
+pub fn orient(image: &DynamicImage, orientation: u16) -> Cow<'_, DynamicImage> {
+    match orientation {
+        2 => Cow::Owned(image.fliph()),
+        3 => Cow::Owned(image.rotate180()),
+        4 => Cow::Owned(image.flipv()),
+        5 => Cow::Owned(image.rotate90().fliph()),
+        6 => Cow::Owned(image.rotate90()),
+        7 => Cow::Owned(image.rotate270().fliph()),
+        8 => Cow::Owned(image.rotate270()),
+        _ => Cow::Borrowed(image),
+    }
+}
+
+/// Data of the field `number` in the first page of the TIFF file in `data`.
+///
+/// Fields stored as 32 bit values, as some writers do for binary blocks,
+/// are returned in the byte order of the file.
+pub fn tiff_field_bytes(data: &[u8], number: u16) -> Option<Vec<u8>> {
+    let tiff = TiffFile::new(data)?;
+    let entry = tiff
+        .first_directory()?
+        .into_iter()
+        .find(|e| e.tag == number)?;
+    match entry.field_type {
+        // BYTE, UNDEFINED and LONG
+        1 | 7 | LONG => Some(entry.value.to_vec()),
+        _ => None,
+    }
+}
+
+/// An entry of a TIFF image file directory along with the data of its value.
+struct Entry<'a> {
+    tag: u16,
+    field_type: u16,
+    value: &'a [u8],
+}
+
+/// A TIFF file in memory whose directories are read entry by entry, so that
+/// large sources are not copied to find a few fields.
+struct TiffFile<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> TiffFile<'a> {
+    /// The TIFF file `data`, `None` if it does not start with a TIFF header.
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = data.starts_with(TIFF_MAGIC[0]);
+        if !little_endian && !data.starts_with(TIFF_MAGIC[1]) {
+            return None;
+        }
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self
+            .data
+            .get(offset..offset.checked_add(2)?)?
+            .try_into()
+            .ok()?;
+        Some(match self.little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self
+            .data
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?;
+        Some(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// The entries of the directory of the first page.
+    fn first_directory(&self) -> Option<Vec<Entry<'a>>> {
+        self.directory(self.u32_at(4)?)
+    }
+
+    /// The entries of the directory at `offset`, leaving out entries of unknown types
+    /// and entries whose value lies outside of the file.
+    fn directory(&self, offset: u32) -> Option<Vec<Entry<'a>>> {
+        let offset = offset as usize;
+        let count = self.u16_at(offset)?;
+        let entries = (0..usize::from(count)).filter_map(|i| {
+            let entry = offset + 2 + i * 12;
+            let field_type = self.u16_at(entry + 2)?;
+            let count = self.u32_at(entry + 4)?;
+            let size = match field_type {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                5 | 10 | 12 => 8,
+                _ => return None,
+            };
+            let length = usize::try_from(count).ok()?.checked_mul(size)?;
+            // Values of up to four bytes are stored in the entry itself.
+            let start = match length <= 4 {
+                true => entry + 8,
+                false => self.u32_at(entry + 8)? as usize,
+            };
+            Some(Entry {
+                tag: self.u16_at(entry)?,
+                field_type,
+                value: self.data.get(start..start.checked_add(length)?)?,
+            })
+        });
+        Some(entries.collect())
+    }
+
+    /// The first number of `entry` if it holds unsigned integers.
+    fn uint(&self, entry: &Entry) -> Option<u32> {
+        match entry.field_type {
+            SHORT => self.value_u16(entry.value).next().map(u32::from),
+            LONG => self.value_u32(entry.value).next(),
+            _ => None,
+        }
+    }
+
+    fn value_u16<'b>(&'b self, value: &'b [u8]) -> impl Iterator<Item = u16> + 'b {
+        value.chunks_exact(2).map(|b| match self.little_endian {
+            true => u16::from_le_bytes([b[0], b[1]]),
+            false => u16::from_be_bytes([b[0], b[1]]),
+        })
+    }
+
+    fn value_u32<'b>(&'b self, value: &'b [u8]) -> impl Iterator<Item = u32> + 'b {
+        value.chunks_exact(4).map(|b| match self.little_endian {
+            true => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            false => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        })
+    }
+
+    /// The EXIF field of `entry`, found in a directory whose fields have `context`.
+    fn field(&self, entry: &Entry, context: Context) -> Option<Field> {
+        let value = entry.value;
+        let longs = || self.value_u32(value);
+        let value = match entry.field_type {
+            1 => Value::Byte(value.to_vec()),
+            2 => {
+                let mut strings: Vec<Vec<u8>> =
+                    value.split(|b| *b == 0).map(<[u8]>::to_vec).collect();
+                if strings.last().is_some_and(Vec::is_empty) {
+                    strings.pop();
+                }
+                Value::Ascii(strings)
+            }
+            SHORT => Value::Short(self.value_u16(value).collect()),
+            LONG => Value::Long(longs().collect()),
+            5 => Value::Rational(
+                longs()
+                    .collect::<Vec<u32>>()
+                    .chunks_exact(2)
+                    .map(|r| (r[0], r[1]).into())
+                    .collect(),
+            ),
+            6 => Value::SByte(value.iter().map(|b| *b as i8).collect()),
+            7 => Value::Undefined(value.to_vec(), 0),
+            8 => Value::SShort(self.value_u16(value).map(|n| n as i16).collect()),
+            9 => Value::SLong(longs().map(|n| n as i32).collect()),
+            10 => Value::SRational(
+                longs()
+                    .collect::<Vec<u32>>()
+                    .chunks_exact(2)
+                    .map(|r| (r[0] as i32, r[1] as i32).into())
+                    .collect(),
+            ),
+            11 => Value::Float(longs().map(f32::from_bits).collect()),
+            12 => Value::Double(
+                value
+                    .chunks_exact(8)
+                    .map(|b| {
+                        let bytes = b.try_into().unwrap_or_default();
+                        match self.little_endian {
+                            true => f64::from_le_bytes(bytes),
+                            false => f64::from_be_bytes(bytes),
+                        }
+                    })
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        Some(Field {
+            tag: Tag(context, entry.tag),
+            ifd_num: In::PRIMARY,
+            value,
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use std::{error::Error, io::Read, path::PathBuf};
+
+/// Extensions of the image formats by the media types servers send for them.
+const MEDIA_TYPE_EXTENSIONS: &[(&str, &str)] = &[
+    ("image/png", "png"),
+    ("image/apng", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/avif", "avif"),
+    ("image/tiff", "tif"),
+    ("image/bmp", "bmp"),
+    ("image/x-icon", "ico"),
+    ("image/vnd.microsoft.icon", "ico"),
+    ("image/svg+xml", "svg"),
+    ("image/x-tga", "tga"),
+    ("image/x-targa", "tga"),
+    ("image/x-exr", "exr"),
+    ("image/x-portable-anymap", "pnm"),
+    ("image/qoi", "qoi"),
+    ("image/heic", "heic"),
+    ("image/heif", "heif"),
+];
+
+/// Download the image at `url` and return a path standing for it with its contents.
+///
+/// The path is the last segment of the URL. Its extension is replaced by the one of the
+/// `Content-Type` of the response if the type is an image format, so formats recognized
+/// by their extension, such as SVG, are decoded even if the URL does not tell the format.
+pub fn fetch(url: &str) -> Result<(PathBuf, Vec<u8>), Box<dyn Error>> {
+    let response = match ureq::get(url).call() {
+        Ok(r) => r,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let media_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_ascii_lowercase());
+
+    let mut data = Vec::new();
+    if let Err(e) = response.into_body().into_reader().read_to_end(&mut data) {
+        return Err(Box::new(e));
+    }
+
+    let name = url.split(['?', '#']).next().unwrap_or(url);
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let mut src = PathBuf::from(name);
+    let extension = media_type.and_then(|t| {
+        MEDIA_TYPE_EXTENSIONS
+            .iter()
+            .find(|(media_type, _)| *media_type == t)
+            .map(|(_, extension)| *extension)
+    });
+    if let Some(e) = extension {
+        src.set_extension(e);
+    }
+    Ok((src, data))
+}
@@ -0,0 +1,69 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use super::{
+    exif::tiff_field_bytes, jpeg_segments, png_chunks, riff_chunks, PNG_SIGNATURE, TIFF_MAGIC,
+};
+
+/// Signature of the JPEG APP2 segments holding an ICC profile.
+const ICC_JPEG_SIGNATURE: &[u8] = b"ICC_PROFILE\0";
+/// TIFF tag holding an ICC profile.
+const ICC_TIFF_TAG: u16 = 34675;
+
+/// Read the ICC profile embedded in the JPEG, PNG, WebP or TIFF image file `data`.
+///
+/// Returns `None` if the source has no ICC profile.
+pub fn read_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        return jpeg_icc_profile(data);
+    }
+    if data.starts_with(PNG_SIGNATURE) {
+        return png_icc_profile(data);
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return riff_chunks(data)
+            .into_iter()
+            .find(|(fourcc, _)| *fourcc == b"ICCP")
+            .map(|(_, body)| body.to_vec());
+    }
+    if TIFF_MAGIC.iter().any(|m| data.starts_with(m)) {
+        return tiff_field_bytes(data, ICC_TIFF_TAG);
+    }
+    None
+}
+
+/// The ICC profile of a JPEG file, which may be split over several APP2 segments.
+fn jpeg_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    // Every segment starts with its sequence number from 1 and the number of segments.
+    let mut chunks: Vec<(u8, &[u8])> = jpeg_segments(data)
+        .into_iter()
+        .filter(|(marker, _)| *marker == 0xe2)
+        .filter_map(|(_, segment)| segment.strip_prefix(ICC_JPEG_SIGNATURE))
+        .filter_map(|chunk| Some((*chunk.first()?, chunk.get(2..)?)))
+        .collect();
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+    Some(
+        chunks
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk.to_vec())
+            .collect(),
+    )
+}
+
+/// The ICC profile of the iCCP chunk of a PNG file.
+fn png_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let (_, body) = png_chunks(data)
+        .into_iter()
+        .find(|(kind, _)| *kind == b"iCCP")?;
+    // Profile name and compression method, then the zlib compressed profile.
+    let name_end = body.iter().position(|b| *b == 0)?;
+    let mut profile = Vec::new();
+    ZlibDecoder::new(body.get(name_end + 2..)?)
+        .read_to_end(&mut profile)
+        .ok()
+        .map(|_| profile)
+}
@@ -0,0 +1,44 @@
+use super::{exif::tiff_field_bytes, jpeg_segments, TIFF_MAGIC};
+use crate::imfconv::handler::metadata::{IPTC_RESOURCE_ID, PHOTOSHOP_JPEG_SIGNATURE};
+
+/// TIFF tag holding IPTC-IIM records.
+const IPTC_TIFF_TAG: u16 = 33723;
+
+/// Read the IPTC-IIM records of the JPEG or TIFF image file `data`.
+///
+/// JPEG files keep the records in a Photoshop resource of their APP13 segment.
+/// Returns `None` if the source has no IPTC records.
+pub fn read_iptc(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        let iptc = jpeg_segments(data)
+            .into_iter()
+            .filter(|(marker, _)| *marker == 0xed)
+            .filter_map(|(_, segment)| segment.strip_prefix(PHOTOSHOP_JPEG_SIGNATURE))
+            .find_map(photoshop_iptc);
+        return iptc;
+    }
+    if TIFF_MAGIC.iter().any(|m| data.starts_with(m)) {
+        return tiff_field_bytes(data, IPTC_TIFF_TAG);
+    }
+    None
+}
+
+/// The IPTC resource among the Photoshop image resources in `data`.
+fn photoshop_iptc(data: &[u8]) -> Option<Vec<u8>> {
+    let mut position = 0;
+    while data.get(position..position + 4) == Some(b"8BIM") {
+        let id = u16::from_be_bytes([*data.get(position + 4)?, *data.get(position + 5)?]);
+        // The name is a Pascal string padded to an even length.
+        let name_length = *data.get(position + 6)? as usize;
+        position += 6 + (name_length + 2) / 2 * 2;
+        let size = data.get(position..position + 4)?;
+        let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        let body = data.get(position + 4..position + 4 + size)?;
+        if id == IPTC_RESOURCE_ID {
+            return Some(body.to_vec());
+        }
+        // Resource data is padded to an even length as well.
+        position += 4 + size + size % 2;
+    }
+    None
+}
@@ -0,0 +1,154 @@
+use std::{error::Error, io::Cursor, path::Path};
+
+use image::{DynamicImage, ImageFormat, Rgb32FImage};
+use rawloader::{RawImage, RawImageData};
+
+/// Extensions of the camera RAW formats handed to rawloader.
+const RAW_EXTENSIONS: &[&str] = &[
+    "3fr", "ari", "arw", "cr2", "crw", "dcr", "dcs", "dng", "erf", "iiq", "kdc", "mef", "mos",
+    "mrw", "nef", "nrw", "orf", "pef", "raf", "rw2", "sr2", "srf", "srw",
+];
+
+/// Linear sRGB (D65) primaries from CIE XYZ.
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.240_454, -1.537_138, -0.498_531],
+    [-0.969_266, 1.876_011, 0.041_556],
+    [0.055_643, -0.204_026, 1.057_225],
+];
+
+/// Whether the file contents `data` read from `src` are a camera RAW file.
+///
+/// RAW files are recognized by the extension of `src`, unless `data` is a known image format
+/// other than TIFF, which most RAW formats are based on.
+pub fn is_raw(src: &Path, data: &[u8]) -> bool {
+    if !matches!(image::guess_format(data), Err(_) | Ok(ImageFormat::Tiff)) {
+        return false;
+    }
+    match src.extension().and_then(|e| e.to_str()) {
+        Some(e) => RAW_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()),
+        None => false,
+    }
+}
+
+/// Develop a camera RAW file into linear light float RGB.
+///
+/// The sensor data is scaled between the black and white levels, white balanced with the
+/// coefficients stored by the camera, demosaiced by averaging the neighbouring photosites
+/// of each color and finally converted from the camera color space to sRGB primaries.
+pub fn read_raw(data: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    let raw = match rawloader::decode(&mut Cursor::new(data)) {
+        Ok(r) => r,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let samples: Vec<f32> = match &raw.data {
+        RawImageData::Integer(d) => d.iter().map(|s| *s as f32).collect(),
+        RawImageData::Float(d) => d.clone(),
+    };
+
+    let x0 = raw.crops[3];
+    let y0 = raw.crops[0];
+    let width = raw.width.saturating_sub(raw.crops[1] + x0);
+    let height = raw.height.saturating_sub(raw.crops[2] + y0);
+    if width == 0 || height == 0 || samples.len() < raw.width * raw.height * raw.cpp {
+        return Err("the RAW image has no pixel data".into());
+    }
+
+    let wb = white_balance(&raw);
+    // Scale a sample of color `c` to 0.0..1.0 and apply the white balance.
+    let level = |value: f32, c: usize| {
+        let black = raw.blacklevels[c] as f32;
+        let white = raw.whitelevels[c] as f32;
+        ((value - black) / (white - black)).clamp(0.0, 1.0) * wb[c]
+    };
+
+    // Camera values of every pixel, in the RGBE order used by rawloader.
+    let mut camera = vec![[0f32; 4]; width * height];
+    if raw.cpp == 3 {
+        for y in 0..height {
+            for x in 0..width {
+                let offset = ((y + y0) * raw.width + x + x0) * 3;
+                let pixel = &mut camera[y * width + x];
+                for c in 0..3 {
+                    pixel[c] = level(samples[offset + c], c);
+                }
+            }
+        }
+    } else if raw.is_monochrome() {
+        for y in 0..height {
+            for x in 0..width {
+                let value = level(samples[(y + y0) * raw.width + x + x0], 0);
+                camera[y * width + x] = [value, value, value, 0.0];
+            }
+        }
+    } else {
+        let cfa = raw.cropped_cfa();
+        let sensor: Vec<f32> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                level(samples[(y + y0) * raw.width + x + x0], cfa.color_at(y, x))
+            })
+            .collect();
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0f32; 4];
+                let mut count = [0u32; 4];
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        let c = cfa.color_at(ny, nx);
+                        sum[c] += sensor[ny * width + nx];
+                        count[c] += 1;
+                    }
+                }
+                let pixel = &mut camera[y * width + x];
+                for c in 0..4 {
+                    if count[c] > 0 {
+                        pixel[c] = sum[c] / count[c] as f32;
+                    }
+                }
+            }
+        }
+    }
+
+    let cam_to_srgb = camera_to_srgb(&raw);
+    let mut image = Rgb32FImage::new(width as u32, height as u32);
+    for (pixel, cam) in image.pixels_mut().zip(camera.iter()) {
+        for (c, row) in cam_to_srgb.iter().enumerate() {
+            let value: f32 = row.iter().zip(cam.iter()).map(|(m, v)| m * v).sum();
+            pixel.0[c] = value.max(0.0);
+        }
+    }
+    Ok(DynamicImage::ImageRgb32F(image))
+}
+
+/// White balance coefficients normalized to green,
+/// or a neutral daylight balance if the camera did not record one.
+fn white_balance(raw: &RawImage) -> [f32; 4] {
+    let mut wb = raw.wb_coeffs;
+    if !wb[..3].iter().all(|c| c.is_finite() && *c > 0.0) {
+        wb = raw.neutralwb();
+    }
+    if !wb[3].is_finite() || wb[3] <= 0.0 {
+        wb[3] = wb[1];
+    }
+    let green = wb[1];
+    wb.map(|c| c / green)
+}
+
+/// Matrix converting white balanced camera RGBE values to linear sRGB.
+fn camera_to_srgb(raw: &RawImage) -> [[f32; 4]; 3] {
+    if raw.is_monochrome() || raw.xyz_to_cam.iter().flatten().all(|v| *v == 0.0) {
+        return [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ];
+    }
+    let cam_to_xyz = raw.cam_to_xyz_normalized();
+    let mut matrix = [[0f32; 4]; 3];
+    for (i, row) in matrix.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| XYZ_TO_SRGB[i][k] * cam_to_xyz[k][j]).sum();
+        }
+    }
+    matrix
+}
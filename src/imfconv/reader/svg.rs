@@ -0,0 +1,75 @@
+use std::{error::Error, path::Path};
+
+use image::{DynamicImage, RgbaImage};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+/// Bytes at the start of an SVG document searched for its root element.
+const SNIFF_LENGTH: usize = 4096;
+
+/// Whether the file contents `data` read from `src` are an SVG document.
+///
+/// Documents starting with XML markup that opens an `svg` element are recognized by their
+/// contents. Other contents count as SVG only if they are no known raster image and `src`
+/// has the extension of an SVG document, e.g. for compressed `.svgz` files.
+pub fn is_svg(src: &Path, data: &[u8]) -> bool {
+    let start = &data[..data.len().min(SNIFF_LENGTH)];
+    let start = start.strip_prefix(b"\xef\xbb\xbf").unwrap_or(start);
+    let markup = start.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<');
+    if markup && start.windows(4).any(|w| w == b"<svg") {
+        return true;
+    }
+    if image::guess_format(data).is_ok() {
+        return false;
+    }
+    match src.extension().and_then(|e| e.to_str()) {
+        Some(e) => e.eq_ignore_ascii_case("svg") || e.eq_ignore_ascii_case("svgz"),
+        None => false,
+    }
+}
+
+/// Rasterize the SVG document in `data` to RGBA, keeping its transparent areas.
+///
+/// The document is rendered at its own size unless `size` gives the width and height
+/// in pixels, in which case it is scaled to fill exactly that size.
+/// Relative references inside the document are resolved from the directory of `src`.
+pub fn read_svg(
+    src: &Path,
+    data: &[u8],
+    size: Option<(u32, u32)>,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let mut options = Options {
+        resources_dir: src.parent().map(Path::to_path_buf),
+        ..Options::default()
+    };
+    options.fontdb_mut().load_system_fonts();
+    let tree = match Tree::from_data(data, &options) {
+        Ok(t) => t,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let intrinsic = tree.size();
+    let (width, height) = match size {
+        Some(s) => s,
+        None => (
+            intrinsic.width().ceil() as u32,
+            intrinsic.height().ceil() as u32,
+        ),
+    };
+    let mut pixmap = match Pixmap::new(width, height) {
+        Some(p) => p,
+        None => return Err("the SVG raster size must not be zero".into()),
+    };
+    let transform = Transform::from_scale(
+        width as f32 / intrinsic.width(),
+        height as f32 / intrinsic.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    match RgbaImage::from_vec(width, height, pixmap.take_demultiplied()) {
+        Some(i) => Ok(DynamicImage::ImageRgba8(i)),
+        None => Err("the SVG could not be rasterized".into()),
+    }
+}
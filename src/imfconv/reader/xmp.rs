@@ -0,0 +1,60 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use super::{
+    exif::tiff_field_bytes, jpeg_segments, png_chunks, riff_chunks, PNG_SIGNATURE, TIFF_MAGIC,
+};
+use crate::imfconv::handler::metadata::XMP_JPEG_NAMESPACE;
+
+/// Keyword of the PNG text chunk holding an XMP packet.
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+/// TIFF tag holding an XMP packet.
+const XMP_TIFF_TAG: u16 = 700;
+
+/// Read the XMP packet of the JPEG, PNG, WebP or TIFF image file `data`.
+///
+/// Returns `None` if the source has no XMP packet. Extended XMP split over
+/// several JPEG segments is not assembled, only the main packet is returned.
+pub fn read_xmp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        let xmp = jpeg_segments(data)
+            .into_iter()
+            .filter(|(marker, _)| *marker == 0xe1)
+            .find_map(|(_, segment)| segment.strip_prefix(XMP_JPEG_NAMESPACE));
+        return xmp.map(<[u8]>::to_vec);
+    }
+    if data.starts_with(PNG_SIGNATURE) {
+        return png_xmp(data);
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return riff_chunks(data)
+            .into_iter()
+            .find(|(fourcc, _)| *fourcc == b"XMP ")
+            .map(|(_, body)| body.to_vec());
+    }
+    if TIFF_MAGIC.iter().any(|m| data.starts_with(m)) {
+        return tiff_field_bytes(data, XMP_TIFF_TAG);
+    }
+    None
+}
+
+/// The XMP packet of the iTXt chunk of a PNG file.
+fn png_xmp(data: &[u8]) -> Option<Vec<u8>> {
+    let (_, body) = png_chunks(data)
+        .into_iter()
+        .find(|(kind, body)| *kind == b"iTXt" && body.starts_with(XMP_PNG_KEYWORD))?;
+    // Keyword, compression flag and method, then language tag and translated keyword.
+    let rest = body.get(XMP_PNG_KEYWORD.len() + 1..)?;
+    let (compressed, rest) = (rest.first() == Some(&1), rest.get(2..)?);
+    let mut parts = rest.splitn(3, |b| *b == 0);
+    let text = parts.nth(2)?;
+    if !compressed {
+        return Some(text.to_vec());
+    }
+    let mut xmp = Vec::new();
+    ZlibDecoder::new(text)
+        .read_to_end(&mut xmp)
+        .ok()
+        .map(|_| xmp)
+}
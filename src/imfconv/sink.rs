@@ -0,0 +1,43 @@
+use std::{error::Error, ffi::OsString, fs, io::Write, path::Path};
+
+/// Destination the encoded files of a conversion are stored to, e.g. a bucket of an
+/// object storage.
+///
+/// `convert` hands every file to `write` with the path derived from the destination
+/// of `Imfconv`, which a sink may map to a key of its own.
+pub trait OutputSink {
+    /// Whether a file is already stored at `path`, for the overwrite policy.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Store the encoded file `data` of the media type `mime_type` at `path`.
+    fn write(&self, path: &Path, data: &[u8], mime_type: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// Sink writing the files to the local file system, the default of `Imfconv`.
+pub struct FileSink;
+impl OutputSink for FileSink {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    /// The file is written to a temporary file in the same directory first and renamed once
+    /// it is complete, so a failed write never leaves a truncated file at the final path.
+    fn write(&self, path: &Path, data: &[u8], _mime_type: &str) -> Result<(), Box<dyn Error>> {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(path.file_name().unwrap_or_default());
+        temp_name.push(format!(".{:08x}.tmp", rand::random::<u32>()));
+        let temp_path = path.with_file_name(temp_name);
+
+        let written = fs::File::create(&temp_path).and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        });
+        match written.and_then(|_| fs::rename(&temp_path, path)) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(Box::new(e))
+            }
+        }
+    }
+}